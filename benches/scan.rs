@@ -0,0 +1,107 @@
+//! 签名扫描性能测试
+//!
+//! 在混有文本、随机字节和真实图片的合成数据上比较逐签名扫描与单次多模式扫描的吞吐量。
+//! 运行: `cargo bench --bench scan`
+
+use std::io::Cursor;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{ImageOutputFormat, RgbImage};
+
+use uhtml_pics_parse::scan::{self, ImageSignature, IMAGE_SIGNATURES};
+
+/// 测试数据大小
+const INPUT_SIZES: &[usize] = &[1 << 20, 16 << 20];
+
+/// 相邻两张图片之间的填充字节数
+const FILLER_LEN: usize = 64 * 1024;
+
+/// 编码一张带渐变的测试图片
+fn encode_image(format: ImageOutputFormat) -> Vec<u8> {
+    let image = RgbImage::from_fn(128, 96, |x, y| image::Rgb([(x * 2) as u8, (y * 2) as u8, (x ^ y) as u8]));
+    let mut encoded = Cursor::new(Vec::new());
+    image.write_to(&mut encoded, format).expect("编码测试图片");
+    encoded.into_inner()
+}
+
+/// 生成约 `size` 字节的合成UHTML数据：HTML文本、随机二进制数据和JPEG/PNG/GIF图片交替出现
+fn synthetic_input(size: usize) -> Vec<u8> {
+    let images = [
+        encode_image(ImageOutputFormat::Jpeg(85)),
+        encode_image(ImageOutputFormat::Png),
+        encode_image(ImageOutputFormat::Gif),
+    ];
+    let text = b"<div class=\"item\"><a href=\"https://example.com/page?id=42\">link</a><p>Lorem ipsum dolor sit amet</p></div>\n";
+
+    let mut data = Vec::with_capacity(size + FILLER_LEN);
+    let mut state: u32 = 0x1234_5678;
+    let mut next = 0;
+    while data.len() < size {
+        // 一半文本一半随机字节，随机字节中会出现 `BM` 等短签名的误报
+        let text_end = data.len() + FILLER_LEN / 2;
+        while data.len() < text_end {
+            data.extend_from_slice(text);
+        }
+        for _ in 0..FILLER_LEN / 2 {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            data.push(state as u8);
+        }
+        data.extend_from_slice(&images[next % images.len()]);
+        next += 1;
+    }
+    data
+}
+
+/// 改进前的做法：每种签名各扫描一遍，每次命中后前进一个字节
+fn naive_find_signatures(data: &[u8]) -> Vec<(usize, &'static ImageSignature)> {
+    let mut positions = Vec::new();
+    for signature in IMAGE_SIGNATURES {
+        let mut pos = 0;
+        while let Some(offset) = data[pos..]
+            .windows(signature.magic.len())
+            .position(|window| window == signature.magic)
+        {
+            let found_pos = pos + offset;
+            if let Some(start) = found_pos.checked_sub(signature.offset) {
+                positions.push((start, signature));
+            }
+            pos = found_pos + 1;
+        }
+    }
+    positions.sort_by_key(|&(pos, _)| pos);
+    positions
+}
+
+fn bench_signature_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signature_search");
+    group.sample_size(10);
+    for &size in INPUT_SIZES {
+        let data = synthetic_input(size);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::new("naive", size), &data, |b, data| {
+            b.iter(|| naive_find_signatures(black_box(data)))
+        });
+        group.bench_with_input(BenchmarkId::new("aho_corasick", size), &data, |b, data| {
+            b.iter(|| scan::find_signatures(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_scan_and_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scan_and_parse");
+    group.sample_size(10);
+    for &size in INPUT_SIZES {
+        let data = synthetic_input(size);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| scan::scan_and_parse(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_signature_search, bench_scan_and_parse);
+criterion_main!(benches);
@@ -0,0 +1,104 @@
+//! 输出格式转换与缩放
+//!
+//! 把解码后的图片重新编码为PNG、JPEG或WebP，供只支持常见格式的查看器使用，
+//! 也用于生成缩小的图片和缩略图。多帧图片（如GIF动画）只保留第一帧；
+//! JPEG不支持透明，透明部分合成到白色背景上。
+
+use std::io::Cursor;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageOutputFormat, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+/// 默认的JPEG编码质量
+pub const DEFAULT_QUALITY: u8 = 85;
+
+/// 输出转换的目标格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertFormat {
+    Png,
+    /// 按 `quality` 有损编码
+    #[value(alias = "jpg")]
+    #[serde(alias = "jpg")]
+    Jpeg,
+    /// 无损编码（`image` 库只支持无损WebP编码）
+    Webp,
+}
+
+impl ConvertFormat {
+    /// 转换后的MIME类型
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ConvertFormat::Png => "image/png",
+            ConvertFormat::Jpeg => "image/jpeg",
+            ConvertFormat::Webp => "image/webp",
+        }
+    }
+
+    /// 重新编码（缩放）图片时使用的格式：PNG、JPEG、WebP保持原格式，其他格式改用PNG
+    pub fn for_reencoding(image_type: &str) -> Self {
+        match image_type {
+            "image/jpeg" => ConvertFormat::Jpeg,
+            "image/webp" => ConvertFormat::Webp,
+            _ => ConvertFormat::Png,
+        }
+    }
+}
+
+/// 保持宽高比缩小到 `max_width` x `max_height` 以内的尺寸，不放大
+pub fn fit_within(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    if width <= max_width && height <= max_height {
+        return (width, height);
+    }
+    let ratio = f64::min(max_width as f64 / width as f64, max_height as f64 / height as f64);
+    let scale = |length: u32| ((length as f64 * ratio).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// 缩放到指定尺寸
+pub fn resize(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    if (image.width(), image.height()) == (width, height) {
+        return image;
+    }
+    image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// 把图片编码为 `format` 格式，`quality`（1-100）只对JPEG生效
+pub fn convert(image: &DynamicImage, format: ConvertFormat, quality: u8) -> Result<Vec<u8>, ImageError> {
+    let (image, output_format) = match format {
+        ConvertFormat::Png => (to_rgb_or_rgba(image), ImageOutputFormat::Png),
+        ConvertFormat::Jpeg => (
+            DynamicImage::ImageRgb8(flatten_on_white(image)),
+            ImageOutputFormat::Jpeg(quality.clamp(1, 100)),
+        ),
+        ConvertFormat::Webp => (to_rgb_or_rgba(image), ImageOutputFormat::WebP),
+    };
+
+    let mut encoded = Cursor::new(Vec::new());
+    image.write_to(&mut encoded, output_format)?;
+    Ok(encoded.into_inner())
+}
+
+/// 转为8位RGB或RGBA（保留透明通道），各编码器都支持这两种像素格式
+fn to_rgb_or_rgba(image: &DynamicImage) -> DynamicImage {
+    if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    }
+}
+
+/// 把透明图片合成到白色背景上
+fn flatten_on_white(image: &DynamicImage) -> RgbImage {
+    if !image.color().has_alpha() {
+        return image.to_rgb8();
+    }
+
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| ((channel as u32 * a as u32 + 255 * (255 - a as u32) + 127) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
@@ -0,0 +1,107 @@
+//! data: URI 解析
+//!
+//! 在HTML/CSS文本中查找以base64内联的图片，
+//! 包括 `src`、`style` 属性和CSS `url()` 中的 `data:image/...;base64,`。
+
+const DATA_URI_PREFIX: &[u8] = b"data:image/";
+
+/// 文本中的一个base64图片data URI
+#[derive(Debug)]
+pub struct DataUri<'a> {
+    /// 小写、不含参数的媒体类型，如 `image/png`
+    pub media_type: String,
+    /// `data:` 在文本中的位置
+    pub offset: usize,
+    /// URI结束位置（不含）
+    pub end: usize,
+    /// 未解码的base64内容
+    pub payload: &'a [u8],
+}
+
+/// 从 `pos` 开始查找下一个base64编码的图片data URI，找到时把 `pos` 移到该URI之后
+///
+/// 非base64的data URI（如百分号编码的SVG）会被忽略。
+/// base64内容在第一个不属于base64字母表的字符（引号、括号、空格等）处结束，
+/// 允许其中夹带换行。
+pub fn next_image_data_uri<'a>(text: &'a [u8], pos: &mut usize) -> Option<DataUri<'a>> {
+    while let Some(offset) = text.get(*pos..)?
+        .windows(DATA_URI_PREFIX.len())
+        .position(|window| window.eq_ignore_ascii_case(DATA_URI_PREFIX))
+    {
+        let start = *pos + offset;
+        *pos = start + DATA_URI_PREFIX.len();
+
+        // data:[<媒体类型>][;参数]*;base64,<内容>
+        let Some(comma) = text[start..].iter().take(256).position(|&b| b == b',') else {
+            continue;
+        };
+        let header = String::from_utf8_lossy(&text[start + "data:".len()..start + comma]).to_ascii_lowercase();
+        let mut params = header.split(';').map(str::trim);
+        let media_type = params.next().unwrap_or("").to_string();
+        if !params.any(|param| param == "base64") || media_type.contains(['"', '\'', '<', '>', ' ']) {
+            continue;
+        }
+
+        let payload_start = start + comma + 1;
+        let payload_len = text[payload_start..]
+            .iter()
+            .position(|&b| !(b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'\r' | b'\n')))
+            .unwrap_or(text.len() - payload_start);
+        let end = payload_start + payload_len;
+        if payload_len == 0 {
+            continue;
+        }
+
+        *pos = end;
+        return Some(DataUri {
+            media_type,
+            offset: start,
+            end,
+            payload: &text[payload_start..end],
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_data_uris(text: &[u8]) -> Vec<DataUri<'_>> {
+        let mut pos = 0;
+        std::iter::from_fn(|| next_image_data_uri(text, &mut pos)).collect()
+    }
+
+    #[test]
+    fn finds_uris_in_attributes_and_css() {
+        let text = b"<img src=\"data:image/png;base64,iVBORw0K\">\
+<div style=\"background: url('DATA:Image/JPEG;charset=utf-8;base64,/9j/\r\n4AAQ')\"></div>";
+        let uris = all_data_uris(text);
+        assert_eq!(uris.len(), 2);
+
+        assert_eq!(uris[0].media_type, "image/png");
+        assert_eq!(uris[0].payload, b"iVBORw0K");
+        assert_eq!(&text[uris[0].offset..uris[0].offset + 5], b"data:");
+        assert_eq!(text[uris[0].end], b'"');
+
+        // 媒体类型不区分大小写，base64内容中允许换行
+        assert_eq!(uris[1].media_type, "image/jpeg");
+        assert_eq!(uris[1].payload, b"/9j/\r\n4AAQ");
+        assert_eq!(text[uris[1].end], b'\'');
+    }
+
+    #[test]
+    fn skips_non_base64_and_empty_uris() {
+        let text = b"<img src=\"data:image/svg+xml,%3Csvg%3E\"><img src=\"data:image/gif;base64,\">\
+<img src=\"data:image/svg+xml;base64,PHN2Zz4=\">";
+        let uris = all_data_uris(text);
+        assert_eq!(uris.len(), 1);
+        assert_eq!(uris[0].media_type, "image/svg+xml");
+        assert_eq!(uris[0].payload, b"PHN2Zz4=");
+    }
+
+    #[test]
+    fn no_uri_in_plain_text() {
+        assert!(all_data_uris(b"<p>data:image/ is mentioned here</p>").is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+//! 按内容哈希去重
+//!
+//! 记录每个图片内容（SHA-256）第一次保存的位置，重复出现时跳过或创建硬链接。
+//! 同一个提取器处理的所有文件共用一份记录，因此批量提取时可以跨文件去重。
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 重复图片的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// 不去重
+    #[default]
+    Off,
+    /// 跳过重复图片
+    Skip,
+    /// 为重复图片创建指向首次保存文件的硬链接
+    Link,
+}
+
+/// 图片内容哈希到首次保存位置的记录
+#[derive(Debug, Default)]
+pub struct DedupIndex {
+    seen: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl DedupIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记图片哈希和保存位置；已登记过时返回首次保存的位置
+    pub fn check_or_insert(&self, hash: &str, path: &Path) -> Option<PathBuf> {
+        let mut seen = self.seen.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match seen.get(hash) {
+            Some(existing) => Some(existing.clone()),
+            None => {
+                seen.insert(hash.to_string(), path.to_path_buf());
+                None
+            }
+        }
+    }
+}
+
+/// 计算图片内容的SHA-256（十六进制小写）
+pub fn content_hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// 创建指向 `original` 的硬链接；无法链接时（如跨文件系统）写入一份副本
+pub fn link_or_copy(original: &Path, path: &Path, data: &[u8]) -> io::Result<()> {
+    // 重复运行时目标文件可能已存在
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    fs::hard_link(original, path).or_else(|_| fs::write(path, data))
+}
@@ -1,11 +1,44 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
 use std::fs;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex, MutexGuard};
+use std::thread;
 use walkdir::WalkDir;
 use anyhow::{Context, Result};
 use thiserror::Error;
 use image::io::Reader as ImageReader;
-use std::io::Cursor;
+use image::{DynamicImage, ImageError, ImageFormat};
+use memchr::memmem;
+use std::io::{Cursor, Read};
+use serde::Serialize;
 
+use crate::convert::{self, ConvertFormat};
+use crate::formats::{ImageSpan, Integrity};
+use crate::datauri::{self, DataUri};
+use crate::dedup::{self, DedupIndex, DedupMode};
+use crate::manifest::{ImageStatus, ManifestEntry, SkipReason};
+use crate::mhtml::{self, MimePart};
+use crate::naming::{self, UniqueNames};
+use crate::options::{ExtractOptions, OverwritePolicy};
+use crate::phash::{self, NearDupAction, NearDupCluster, NearDupIndex};
+use crate::progress::ProgressLog;
+use crate::repair::{self, Repair};
+use crate::signatures::{ImageSignature, SignatureCursor, IMAGE_SIGNATURES};
+use crate::source::SourceData;
+
+/// 隔离无法解码的图片的子目录名（位于输出目录下）
+const QUARANTINE_DIR: &str = "_quarantine";
+
+/// 缩略图的子目录名（位于输出目录下）
+const THUMBNAIL_DIR: &str = "_thumbnails";
+
+/// 并行提取时等待保存轮次期间最多预先检查的图片数据量（仅计解码或修复产生的数据）
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// 提取过程中的错误
 #[derive(Error, Debug)]
 pub enum ExtractionError {
     #[error("文件读取失败")]
@@ -16,46 +49,290 @@ pub enum ExtractionError {
     PathError(String),
     #[error("图片解析失败")]
     ImageParse(String),
+    #[error("命名模板错误: {0}")]
+    NameTemplate(String),
+    #[error("宽高比范围错误: {0}")]
+    AspectRange(String),
 }
 
+/// 从UHTML数据中找到的一张图片
 #[derive(Debug)]
-pub struct ImageInfo {
-    #[allow(dead_code)]
+pub struct ImageInfo<'a> {
+    /// 通过过滤的图片中的序号，从0开始
     pub index: usize,
+    /// MIME类型，如 `image/jpeg`
     pub image_type: String,
+    /// 图片数据字节数
     pub size: usize,
-    pub data: Vec<u8>,
-    #[allow(dead_code)]
+    /// 图片数据；未编码的图片直接借用源文件（内存映射）中的字节
+    pub data: Cow<'a, [u8]>,
+    /// 在源文件中的起止字节偏移（编码过的MIME部分为整个部分的范围）
     pub position: usize,
-    #[allow(dead_code)]
     pub end_position: usize,
-    pub width: u32,
-    pub height: u32,
+    /// 像素尺寸，无法解析时（如SVG）为 `None`
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 数据完整性判定（结构解析与校验的结果）
+    pub integrity: Integrity,
+    /// 修复方式，未修复时为 `None`（修复后 `data`、`size`、`hash` 均为修复后的数据）
+    pub repair: Option<Repair>,
+    /// 帧数（GIF动画等多帧格式，未解析时为 `None`）
+    pub frame_count: Option<u32>,
+    /// 来源MIME部分的 `Content-Location`
+    pub content_location: Option<String>,
+    /// 来源MIME部分声明的文件名（`Content-Disposition`）
+    pub original_filename: Option<String>,
+    /// 图片内容的SHA-256（十六进制小写）
+    pub hash: String,
+    /// 感知哈希（dHash），仅在启用相似图片检测且能解码时计算
+    pub perceptual_hash: Option<u64>,
 }
 
-#[derive(Debug)]
+impl ImageInfo<'_> {
+    /// 像素尺寸（宽, 高），无法解析时为 `None`
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.width.zip(self.height)
+    }
+
+    /// 复制借用的图片数据，得到不依赖源数据生命周期的图片信息
+    pub fn into_owned(self) -> ImageInfo<'static> {
+        ImageInfo {
+            data: Cow::Owned(self.data.into_owned()),
+            ..self
+        }
+    }
+}
+
+/// 单个UHTML文件的提取结果
+#[derive(Debug, Serialize)]
 pub struct ExtractionResult {
     pub source_file: PathBuf,
     pub output_directory: PathBuf,
+    /// 通过过滤的图片数
     pub total_images: usize,
+    /// 实际写入输出目录的图片数
     pub saved_images: usize,
+    /// 与之前保存过的图片内容相同、被跳过或硬链接的图片数
+    pub duplicate_images: usize,
+    /// 与之前的图片相似（感知哈希距离不超过阈值）的图片数
+    pub near_duplicate_images: usize,
+    /// 批量提取时该文件处理失败的原因
     pub error: Option<String>,
+    /// 每张图片（包括被跳过的图片）的清单记录，按在源文件中的位置排序
+    #[serde(rename = "images")]
+    pub manifest: Vec<ManifestEntry>,
+}
+
+/// 单张图片的保存结果
+enum SaveOutcome {
+    /// 已保存；启用相似图片检测时附带相似的代表图片及距离
+    Saved { path: PathBuf, similar_to: Option<(PathBuf, u32)> },
+    /// 与 `original` 内容相同，已创建硬链接（或副本）
+    Linked { path: PathBuf, original: PathBuf },
+    /// 与 `original` 内容相同，已跳过
+    Skipped { original: PathBuf },
+    /// 与 `original` 相似，已跳过
+    NearDuplicate { original: PathBuf, distance: u32 },
+    /// 输出文件已存在，按覆盖策略跳过
+    Exists { path: PathBuf },
+}
+
+/// 并行批量提取时各文件轮流保存的顺序
+struct SaveOrder {
+    /// 轮到保存的文件序号
+    next: Mutex<usize>,
+    turn_changed: Condvar,
+}
+
+impl SaveOrder {
+    fn new() -> Self {
+        Self {
+            next: Mutex::new(0),
+            turn_changed: Condvar::new(),
+        }
+    }
+
+    /// 等待轮到第 `file` 个文件
+    fn wait_for(&self, file: usize) -> MutexGuard<'_, usize> {
+        let mut next = self.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while *next != file {
+            next = self.turn_changed.wait(next).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        next
+    }
 }
 
-pub struct UHTMLImageExtractor;
+/// 一个文件的保存轮次；释放时（包括处理失败提前返回时）轮到下一个文件
+///
+/// 文件按序号从小到大分配给工作线程，等待的文件之前的文件都已在处理中，不会死锁。
+struct SaveTurn<'o> {
+    order: &'o SaveOrder,
+    file: usize,
+}
+
+impl<'o> SaveTurn<'o> {
+    fn new(order: &'o SaveOrder, file: usize) -> Self {
+        Self { order, file }
+    }
+
+    /// 之前的文件是否都已保存完成
+    fn is_ready(&self) -> bool {
+        *self.order.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) == self.file
+    }
+
+    /// 等待之前的文件都保存完成
+    fn wait(&self) {
+        drop(self.order.wait_for(self.file));
+    }
+}
+
+impl Drop for SaveTurn<'_> {
+    fn drop(&mut self) {
+        let mut next = self.order.wait_for(self.file);
+        *next += 1;
+        self.order.turn_changed.notify_all();
+    }
+}
+
+/// 单张图片的提取结果
+struct SingleImage<'a> {
+    data: Cow<'a, [u8]>,
+    /// MIME类型，如 `image/jpeg`
+    image_type: String,
+    /// 在UHTML数据中的起止位置
+    position: usize,
+    end_position: usize,
+    /// 边界由结构解析确定时为解析结果，否则为 `Unknown`（根据结束标记或相邻图片估计）
+    integrity: Integrity,
+    /// 类型由MIME部分或data URI声明，而不是由签名扫描推测
+    declared_type: bool,
+    frame_count: Option<u32>,
+    content_location: Option<String>,
+    original_filename: Option<String>,
+}
+
+impl SingleImage<'_> {
+    /// 被过滤掉时的清单记录
+    fn skipped(&self, hash: &str, reason: SkipReason, dimensions: Option<(u32, u32)>) -> ManifestEntry {
+        ManifestEntry {
+            index: None,
+            position: self.position,
+            end_position: self.end_position,
+            image_type: self.image_type.clone(),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            size: self.data.len(),
+            hash: hash.to_string(),
+            integrity: self.integrity,
+            repair: None,
+            status: ImageStatus::Skipped,
+            skip_reason: Some(reason),
+            output_path: None,
+            converted_to: None,
+            resized_width: None,
+            resized_height: None,
+            thumbnail_path: None,
+            duplicate_of: None,
+            similar_to: None,
+            distance: None,
+            error: None,
+        }
+    }
+}
+
+/// UHTML图片提取器
+///
+/// 提取选项由 [`ExtractOptions`] 指定。去重和相似图片检测的记录保存在提取器中，
+/// 同一个提取器处理的所有文件共用，提取器可以在多个线程间共享。
+pub struct UHTMLImageExtractor {
+    options: ExtractOptions,
+    /// 已保存图片的内容哈希，跨文件共用
+    dedup_index: DedupIndex,
+    /// 相似图片聚类记录，未启用相似图片检测时为 `None`
+    near_dup_index: Option<NearDupIndex>,
+}
 
 impl UHTMLImageExtractor {
+    /// 使用默认选项创建提取器
     pub fn new() -> Self {
-        Self
+        Self::with_options(ExtractOptions::default())
+    }
+
+    /// 使用指定选项创建提取器
+    pub fn with_options(options: ExtractOptions) -> Self {
+        Self {
+            near_dup_index: options
+                .near_dup_threshold
+                .map(|threshold| NearDupIndex::new(threshold, options.near_dup_action)),
+            dedup_index: DedupIndex::new(),
+            options,
+        }
+    }
+
+    /// 提取选项
+    pub fn options(&self) -> &ExtractOptions {
+        &self.options
+    }
+
+    /// 目前为止发现的相似图片组（两张及以上）
+    pub fn near_duplicate_clusters(&self) -> Vec<NearDupCluster> {
+        self.near_dup_index
+            .as_ref()
+            .map(NearDupIndex::clusters)
+            .unwrap_or_default()
+    }
+
+    /// 逐张产出内存中UHTML数据里的图片
+    ///
+    /// 图片在迭代到时才解码和解析，调用方可以随时停止迭代（例如只取第一张图片做缩略图），
+    /// 内嵌大量图片的文件也不会一次性占用全部图片的内存。过滤规则和副作用与
+    /// [`extract_images_from_bytes`](Self::extract_images_from_bytes) 相同。
+    pub fn images<'a>(&self, data: &'a [u8]) -> ImageIter<'_, 'a> {
+        ImageIter::new(self, data, ProgressLog::silent())
+    }
+
+    /// 按 `quiet` 选项创建直接输出或不输出的提示信息
+    fn progress_log(&self) -> ProgressLog {
+        if self.options.quiet {
+            ProgressLog::silent()
+        } else {
+            ProgressLog::immediate()
+        }
+    }
+
+    /// 从内存中的UHTML数据提取图片
+    ///
+    /// 按选项中的格式和尺寸过滤，返回通过过滤的图片；不创建目录、不写入文件、不输出提示信息，
+    /// 也不计入去重和相似图片检测的记录。未编码的图片直接借用 `data` 中的字节。
+    pub fn extract_images_from_bytes<'a>(&self, data: &'a [u8]) -> Result<Vec<ImageInfo<'a>>> {
+        Ok(self.images(data).collect())
+    }
+
+    /// 从 `reader` 读取全部UHTML数据并提取图片，与 [`extract_images_from_bytes`](Self::extract_images_from_bytes) 相同不触及文件系统
+    pub fn extract_images_from_reader(&self, mut reader: impl Read) -> Result<Vec<ImageInfo<'static>>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).context("读取UHTML数据失败")?;
+        let images = self.extract_images_from_bytes(&data)?;
+        Ok(images.into_iter().map(ImageInfo::into_owned).collect())
     }
 
     /// 从单个UHTML文件提取所有图片
-    pub fn extract_images_from_file(
+    ///
+    /// `output_dir` 为 `None` 时输出到与文件同名的目录。
+    pub fn extract_images_from_file(&self, uhtml_path: &Path, output_dir: Option<&PathBuf>) -> Result<ExtractionResult> {
+        self.extract_file(uhtml_path, output_dir, &mut self.progress_log(), None)
+    }
+
+    /// 从单个UHTML文件提取所有图片，提示信息写入 `log`
+    ///
+    /// 指定 `turn` 时等待轮到该文件再保存，使跨文件的去重和相似图片判定按文件顺序进行；
+    /// 等待期间预先检查后续图片，暂存的解码数据超过 `MAX_PENDING_BYTES` 后停止，避免整个文件的图片同时驻留内存。
+    fn extract_file(
         &self,
         uhtml_path: &Path,
         output_dir: Option<&PathBuf>,
-        output_all: bool,
-        min_size: Option<(u32, u32)>,
+        log: &mut ProgressLog,
+        turn: Option<&SaveTurn>,
     ) -> Result<ExtractionResult> {
         if !uhtml_path.exists() {
             return Err(ExtractionError::PathError(format!("文件不存在: {}", uhtml_path.display())).into());
@@ -74,55 +351,182 @@ impl UHTMLImageExtractor {
             }
         };
 
-        // 创建输出目录
-        fs::create_dir_all(&output_dir)
-            .with_context(|| format!("创建输出目录失败: {}", output_dir.display()))?;
-
-        // 读取文件数据
-        let data = fs::read(uhtml_path)
+        // 以内存映射方式打开文件，图片直接从映射的字节范围写出
+        let data = SourceData::open(uhtml_path)
             .with_context(|| format!("读取文件失败: {}", uhtml_path.display()))?;
 
-        // 提取图片
-        let images = self.extract_images(&data, output_all, min_size)?;
-
-        // 保存图片
+        // 逐张提取并保存，解码得到的图片数据保存后即释放
+        let mut images = ImageIter::new(self, &data, mem::take(log));
+        let mut filtered_entries = Vec::new();
+        let mut saved_entries = Vec::new();
         let mut saved_count = 0;
-        for (i, image) in images.iter().enumerate() {
-            match self.save_image(&output_dir, i, image) {
-                Ok(path) => {
-                    println!("保存图片: {} ({}x{}, {} bytes)", 
-                             path.display(), image.width, image.height, image.size);
+        let mut duplicate_count = 0;
+        let mut near_duplicate_count = 0;
+        let source_stem = uhtml_path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+        let mut names = UniqueNames::new();
+        let mut quarantine_names = UniqueNames::new();
+        let (saved_status, verb) = if self.options.dry_run {
+            (ImageStatus::Planned, "将")
+        } else {
+            (ImageStatus::Saved, "")
+        };
+        let mut pending = VecDeque::new();
+        if let Some(turn) = turn {
+            let mut pending_bytes = 0;
+            while pending_bytes < MAX_PENDING_BYTES && !turn.is_ready() {
+                let Some(examined) = images.next_examined() else {
+                    break;
+                };
+                pending_bytes += examined.owned_len();
+                pending.push_back(examined);
+            }
+            turn.wait();
+        }
+        while let Some(examined) = pending.pop_front().or_else(|| images.next_examined()) {
+            let image = match examined {
+                Examined::Accepted(image) => image,
+                Examined::Rejected(entry, data) => {
+                    let entry = match data.filter(|_| self.options.quarantine) {
+                        Some(data) => {
+                            let quarantine_dir = output_dir.join(QUARANTINE_DIR);
+                            self.quarantine_image(uhtml_path, &quarantine_dir, entry, &data, &mut quarantine_names, images.log())
+                        }
+                        None => entry,
+                    };
+                    filtered_entries.push(entry);
+                    continue;
+                }
+            };
+
+            let log = images.log();
+            let i = image.index;
+            let converted_to = self.conversion_target(&image);
+            let entry = match self.save_image(&output_dir, &source_stem, i, &image, converted_to, &mut names) {
+                Ok(SaveOutcome::Linked { path, original }) => {
+                    log.info(format!("{}链接重复图片: {} -> {}", verb, path.display(), original.display()));
+                    duplicate_count += 1;
+                    let status = if self.options.dry_run { ImageStatus::Planned } else { ImageStatus::Linked };
+                    let (resized_width, resized_height) = self.resized_dimensions(&image).unzip();
+                    ManifestEntry {
+                        output_path: Some(path),
+                        duplicate_of: Some(original),
+                        converted_to,
+                        resized_width,
+                        resized_height,
+                        ..ManifestEntry::for_image(&image, status)
+                    }
+                }
+                Ok(SaveOutcome::Skipped { original }) => {
+                    log.info(format!("跳过重复图片: 第 {} 张与 {} 相同", i, original.display()));
+                    duplicate_count += 1;
+                    ManifestEntry {
+                        skip_reason: Some(SkipReason::Duplicate),
+                        duplicate_of: Some(original),
+                        ..ManifestEntry::for_image(&image, ImageStatus::Skipped)
+                    }
+                }
+                Ok(SaveOutcome::NearDuplicate { original, distance }) => {
+                    log.info(format!("跳过相似图片: 第 {} 张与 {} 相似 (距离 {})", i, original.display(), distance));
+                    near_duplicate_count += 1;
+                    ManifestEntry {
+                        skip_reason: Some(SkipReason::NearDuplicate),
+                        similar_to: Some(original),
+                        distance: Some(distance),
+                        ..ManifestEntry::for_image(&image, ImageStatus::Skipped)
+                    }
+                }
+                Ok(SaveOutcome::Exists { path }) => {
+                    log.info(format!("跳过已存在的文件: {}", path.display()));
+                    ManifestEntry {
+                        skip_reason: Some(SkipReason::AlreadyExists),
+                        output_path: Some(path),
+                        ..ManifestEntry::for_image(&image, ImageStatus::Skipped)
+                    }
+                }
+                Ok(SaveOutcome::Saved { path, similar_to }) => {
+                    log.info(format!("{}保存图片: {} ({}, {} bytes)", 
+                             verb, path.display(), format_dimensions(image.dimensions()), image.size));
+                    if let Some((original, distance)) = &similar_to {
+                        log.info(format!("  相似图片: 与 {} 相似 (距离 {})", original.display(), distance));
+                        near_duplicate_count += 1;
+                    }
+                    let resized = self.resized_dimensions(&image);
+                    if let Some((width, height)) = resized {
+                        log.info(format!("  缩小尺寸: {} -> {}x{}", format_dimensions(image.dimensions()), width, height));
+                    }
+                    if let Some(format) = converted_to.filter(|format| format.mime_type() != image.image_type) {
+                        log.info(format!("  转换格式: {} -> {}", image.image_type, format.mime_type()));
+                    }
+                    let thumbnail_path = self
+                        .options
+                        .thumbnail
+                        .and_then(|max_size| self.save_thumbnail(&path, &image, converted_to, max_size, log));
+                    if let Some(thumbnail_path) = &thumbnail_path {
+                        log.info(format!("  缩略图: {}", thumbnail_path.display()));
+                    }
+                    if let Some(frames) = image.frame_count.filter(|&frames| frames > 1) {
+                        log.info(format!("  动画: {} 帧", frames));
+                    }
+                    if image.integrity == Integrity::Damaged {
+                        log.info(format!("  警告: 图片数据校验失败（{}），文件可能无法正常打开", image.integrity.label()));
+                    }
                     saved_count += 1;
+                    let (similar_to, distance) = similar_to.unzip();
+                    let (resized_width, resized_height) = resized.unzip();
+                    ManifestEntry {
+                        output_path: Some(path),
+                        similar_to,
+                        distance,
+                        converted_to,
+                        resized_width,
+                        resized_height,
+                        thumbnail_path,
+                        ..ManifestEntry::for_image(&image, saved_status)
+                    }
                 }
                 Err(e) => {
-                    eprintln!("保存图片 {} 失败: {}", i, e);
+                    log.warn(format!("保存图片 {} 失败: {}", i, e));
+                    ManifestEntry {
+                        skip_reason: Some(SkipReason::SaveFailed),
+                        error: Some(format!("{:#}", e)),
+                        ..ManifestEntry::for_image(&image, ImageStatus::Failed)
+                    }
                 }
-            }
+            };
+            saved_entries.push(entry);
         }
+        let total_images = images.yielded;
+        *log = images.finish();
+        let mut manifest = filtered_entries;
+        manifest.extend(saved_entries);
+        manifest.sort_by_key(|entry| entry.position);
 
         Ok(ExtractionResult {
             source_file: uhtml_path.to_path_buf(),
             output_directory: output_dir,
-            total_images: images.len(),
+            total_images,
             saved_images: saved_count,
+            duplicate_images: duplicate_count,
+            near_duplicate_images: near_duplicate_count,
             error: None,
+            manifest,
         })
     }
 
     /// 批量提取目录下所有UHTML文件中的图片
+    ///
+    /// 每个文件输出到与其同名的目录；单个文件失败不会中断批量提取，错误记录在对应结果的 `error` 中。
     pub fn extract_images_from_directory(
         &self,
         directory: &Path,
         recursive: bool,
-        output_all: bool,
-        min_size: Option<(u32, u32)>,
     ) -> Result<Vec<ExtractionResult>> {
         if !directory.exists() || !directory.is_dir() {
             return Err(ExtractionError::PathError(format!("目录不存在或不是有效目录: {}", directory.display())).into());
         }
 
-        // 查找所有UHTML文件
-        let uhtml_files: Vec<PathBuf> = if recursive {
+        // 查找所有UHTML文件，按路径排序使处理顺序（以及去重时保留哪个文件中的图片）不随文件系统变化
+        let mut uhtml_files: Vec<PathBuf> = if recursive {
             WalkDir::new(directory)
                 .into_iter()
                 .filter_map(|e| e.ok())
@@ -130,159 +534,541 @@ impl UHTMLImageExtractor {
                 .filter(|e| {
                     e.path()
                         .extension()
-                        .map_or(false, |ext| ext == "uhtml")
+                        .is_some_and(|ext| ext == "uhtml")
                 })
                 .map(|e| e.path().to_path_buf())
                 .collect()
         } else {
             fs::read_dir(directory)?
                 .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().map_or(false, |ft| ft.is_file()))
+                .filter(|e| e.file_type().is_ok_and(|ft| ft.is_file()))
                 .filter(|e| {
                     e.path()
                         .extension()
-                        .map_or(false, |ext| ext == "uhtml")
+                        .is_some_and(|ext| ext == "uhtml")
                 })
                 .map(|e| e.path())
                 .collect()
         };
+        uhtml_files.sort();
 
+        let mut log = self.progress_log();
         if uhtml_files.is_empty() {
-            println!("在目录 {} 中未找到UHTML文件", directory.display());
+            log.info(format!("在目录 {} 中未找到UHTML文件", directory.display()));
             return Ok(vec![]);
         }
 
-        println!("找到 {} 个UHTML文件，开始提取...", uhtml_files.len());
+        log.info(format!("找到 {} 个UHTML文件，开始提取...", uhtml_files.len()));
 
-        let mut results = Vec::new();
-        for uhtml_file in uhtml_files {
-            println!("\n处理文件: {}", uhtml_file.display());
-            
-            match self.extract_images_from_file(&uhtml_file, None, output_all, min_size) {
-                Ok(result) => {
-                    println!("✓ 完成: 提取了 {} 张图片", result.saved_images);
+        let jobs = match self.options.jobs {
+            0 => thread::available_parallelism().map_or(1, |parallelism| parallelism.get()),
+            jobs => jobs,
+        }
+        .min(uhtml_files.len());
+        if jobs <= 1 {
+            let results = uhtml_files
+                .iter()
+                .map(|uhtml_file| self.extract_batch_file(uhtml_file, &mut log, None))
+                .collect();
+            return Ok(results);
+        }
+
+        log.info(format!("并行处理: {} 个线程", jobs));
+        Ok(self.extract_files_parallel(&uhtml_files, jobs))
+    }
+
+    /// 用 `jobs` 个工作线程并行提取多个文件，结果按 `uhtml_files` 的顺序返回
+    ///
+    /// 每个文件的提示信息先缓存，轮到该文件时再整体输出，因此输出顺序与顺序处理时一致。
+    /// 启用去重或相似图片检测时，各文件并行检查图片，但按文件顺序轮流保存，保存结果与顺序处理时相同。
+    fn extract_files_parallel(&self, uhtml_files: &[PathBuf], jobs: usize) -> Vec<ExtractionResult> {
+        let next_file = AtomicUsize::new(0);
+        let save_order = SaveOrder::new();
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            for _ in 0..jobs {
+                let sender = sender.clone();
+                let next_file = &next_file;
+                let save_order = &save_order;
+                scope.spawn(move || loop {
+                    let i = next_file.fetch_add(1, Ordering::Relaxed);
+                    let Some(uhtml_file) = uhtml_files.get(i) else {
+                        break;
+                    };
+                    let mut log = if self.options.quiet { ProgressLog::silent() } else { ProgressLog::buffered() };
+                    let turn = self.shares_save_decisions().then(|| SaveTurn::new(save_order, i));
+                    let result = self.extract_batch_file(uhtml_file, &mut log, turn.as_ref());
+                    drop(turn);
+                    if sender.send((i, result, log)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            // 先完成的文件等排在它前面的文件都输出后再输出
+            let mut pending = BTreeMap::new();
+            let mut results = Vec::with_capacity(uhtml_files.len());
+            for (i, result, log) in receiver {
+                pending.insert(i, (result, log));
+                while let Some((result, mut log)) = pending.remove(&results.len()) {
+                    log.flush();
                     results.push(result);
                 }
-                Err(e) => {
-                    let error_result = ExtractionResult {
-                        source_file: uhtml_file.clone(),
-                        output_directory: PathBuf::new(),
-                        total_images: 0,
-                        saved_images: 0,
-                        error: Some(e.to_string()),
-                    };
-                    println!("✗ 失败: {}", e);
-                    results.push(error_result);
+            }
+            results
+        })
+    }
+
+    /// 保存判定是否依赖之前保存过的图片（去重或相似图片检测），并行处理时需要按文件顺序保存
+    fn shares_save_decisions(&self) -> bool {
+        self.options.dedup != DedupMode::Off || self.near_dup_index.is_some()
+    }
+
+    /// 批量提取中处理单个文件，失败时返回带错误信息的结果
+    fn extract_batch_file(&self, uhtml_file: &Path, log: &mut ProgressLog, turn: Option<&SaveTurn>) -> ExtractionResult {
+        log.info(format!("\n处理文件: {}", uhtml_file.display()));
+
+        match self.extract_file(uhtml_file, None, log, turn) {
+            Ok(result) => {
+                log.info(format!("✓ 完成: 提取了 {} 张图片", result.saved_images));
+                result
+            }
+            Err(e) => {
+                log.info(format!("✗ 失败: {}", e));
+                ExtractionResult {
+                    source_file: uhtml_file.to_path_buf(),
+                    output_directory: PathBuf::new(),
+                    total_images: 0,
+                    saved_images: 0,
+                    duplicate_images: 0,
+                    near_duplicate_images: 0,
+                    error: Some(e.to_string()),
+                    manifest: Vec::new(),
                 }
             }
         }
-
-        Ok(results)
     }
 
-    /// 从UHTML数据中提取所有图片
-    fn extract_images(&self, data: &[u8], output_all: bool, min_size: Option<(u32, u32)>) -> Result<Vec<ImageInfo>> {
-        let mut images = Vec::new();
+    /// 把MIME部分解码为候选图片
+    ///
+    /// 声明为 `image/*` 的部分按声明类型返回；其他非文本部分（如 `application/octet-stream`）
+    /// 如果解码后能按已知图片格式解析，也按识别出的类型返回，否则返回 `None`。
+    fn mime_part_image<'a>(&self, part: &MimePart<'a>, log: &mut ProgressLog) -> Option<SingleImage<'a>> {
+        let body = match part.decode_body() {
+            Ok(body) => body,
+            Err(e) => {
+                log.warn(format!("解码MIME部分失败 ({}): {}", self.describe_part(part), e));
+                return None;
+            }
+        };
 
-        // 图片格式签名和结束标记
-        let image_signatures = [
-            (&b"\xFF\xD8\xFF"[..], "jpeg", &b"\xFF\xD9"[..]),          // JPEG
-            (&b"\x89PNG\r\n\x1a\n"[..], "png", &b"IEND\xaeB`\x82"[..]), // PNG
-            (&b"GIF87a"[..], "gif", &b"\x00\x3B"[..]),                  // GIF87a
-            (&b"GIF89a"[..], "gif", &b"\x00\x3B"[..]),                  // GIF89a
-        ];
+        let span = self.parse_image_structure(&body);
+        let declared_type = part.content_type.starts_with("image/");
+        let image_type = if declared_type {
+            self.normalize_image_type(&part.content_type)
+        } else {
+            let (signature, span) = span?;
+            format!("image/{}", span.image_type.unwrap_or(signature.image_type))
+        };
 
-        let mut all_positions = Vec::new();
+        Some(SingleImage {
+            image_type,
+            position: part.offset,
+            end_position: part.offset + part.raw_body.len(),
+            integrity: span.map_or(Integrity::Unknown, |(_, span)| span.integrity),
+            declared_type,
+            frame_count: span.and_then(|(_, span)| span.frame_count),
+            content_location: part.content_location.clone(),
+            original_filename: part.filename.clone(),
+            data: body,
+        })
+    }
 
-        // 查找所有图片位置
-        for (header, img_type, footer) in &image_signatures {
-            let mut pos = 0;
-            while let Some(found_pos) = self.find_bytes(data, header, pos) {
-                all_positions.push((found_pos, *img_type, *header, *footer));
-                pos = found_pos + 1;
+    /// 解码base64图片data URI，`position` / `end_position` 为其在UHTML数据中的位置
+    fn data_uri_image(
+        &self,
+        uri: &DataUri,
+        position: usize,
+        end_position: usize,
+        log: &mut ProgressLog,
+    ) -> Option<SingleImage<'static>> {
+        let data = match mhtml::decode_base64(uri.payload) {
+            Ok(data) => data,
+            Err(e) => {
+                log.warn(format!("解码data URI失败 (偏移 {}): {}", uri.offset, e));
+                return None;
             }
-        }
+        };
 
-        // 按位置排序
-        all_positions.sort_by_key(|&(pos, _, _, _)| pos);
-
-        // 提取每张图片
-        for (i, &(pos, img_type, _header, footer)) in all_positions.iter().enumerate() {
-            if let Ok(image_data) = self.extract_single_image(data, pos, img_type, footer, &all_positions, i) {
-                // 验证图片数据（最小大小检查）
-                if image_data.len() >= 100 {  // 图片至少100字节
-                    // 获取图片尺寸
-                    let (width, height) = self.get_image_dimensions(&image_data)?;
-                    
-                    // 如果不是输出全部，过滤小于指定尺寸的图片
-                    let (min_width, min_height) = min_size.unwrap_or((100, 100));
-                    if !output_all && width < min_width && height < min_height {
-                        println!("跳过小图片: {}x{} 像素 (最小尺寸: {}x{})", width, height, min_width, min_height);
-                        continue;
-                    }
-                    
-                    let data_size = image_data.len();
-                    let image = ImageInfo {
-                        index: images.len(),
-                        image_type: format!("image/{}", img_type),
-                        size: data_size,
-                        data: image_data,
-                        position: pos,
-                        end_position: pos + data_size,
-                        width,
-                        height,
-                    };
-                    images.push(image);
-                }
-            }
+        let span = self.parse_image_structure(&data);
+        Some(SingleImage {
+            image_type: self.normalize_image_type(&uri.media_type),
+            position,
+            end_position,
+            integrity: span.map_or(Integrity::Unknown, |(_, span)| span.integrity),
+            declared_type: true,
+            frame_count: span.and_then(|(_, span)| span.frame_count),
+            content_location: None,
+            original_filename: None,
+            data: Cow::Owned(data),
+        })
+    }
+
+    /// 按数据开头的签名做结构解析
+    fn parse_image_structure(&self, data: &[u8]) -> Option<(&'static ImageSignature, ImageSpan)> {
+        IMAGE_SIGNATURES
+            .iter()
+            .filter(|signature| data.get(signature.offset..).is_some_and(|rest| rest.starts_with(signature.magic)))
+            .find_map(|signature| (signature.parse)(data, 0).map(|span| (signature, span)))
+    }
+
+    /// 规范化声明的图片类型（部分页面使用非标准写法）
+    fn normalize_image_type(&self, content_type: &str) -> String {
+        match content_type {
+            "image/jpg" | "image/pjpeg" => "image/jpeg".to_string(),
+            "image/x-png" => "image/png".to_string(),
+            "image/vnd.microsoft.icon" => "image/x-icon".to_string(),
+            _ => content_type.to_string(),
         }
+    }
 
-        Ok(images)
+    /// 用于提示信息的MIME部分描述
+    fn describe_part(&self, part: &MimePart) -> String {
+        part.content_location
+            .clone()
+            .or_else(|| part.content_id.clone())
+            .unwrap_or_else(|| format!("偏移 {}", part.offset))
     }
 
     /// 提取单张图片
-    fn extract_single_image(
+    fn extract_single_image<'a>(
         &self,
-        data: &[u8],
+        data: &'a [u8],
         start_pos: usize,
-        img_type: &str,
-        footer: &[u8],
-        all_positions: &[(usize, &str, &[u8], &[u8])],
+        signature: &ImageSignature,
+        positions: &mut SignatureCursor,
         current_index: usize,
-    ) -> Result<Vec<u8>> {
-        // 查找图片结束位置
-        let end_pos = if let Some(footer_pos) = self.find_bytes(data, footer, start_pos) {
-            match img_type {
-                "jpeg" => footer_pos + 2,  // JPEG结束标记长度为2
-                "png" => footer_pos + 8,   // PNG IEND块长度为8
-                "gif" => footer_pos + 2,   // GIF结束标记长度为2
-                _ => footer_pos + footer.len(),
+    ) -> Result<SingleImage<'a>> {
+        // 优先按格式结构确定结束位置
+        let span = (signature.parse)(data, start_pos);
+
+        // 结构解析失败时回退到查找结束标记；没有结束标记的格式不做猜测
+        let end_pos = if let Some(span) = span {
+            span.end
+        } else if let Some(footer) = signature.footer {
+            if let Some(footer_pos) = self.find_bytes(data, footer, start_pos) {
+                footer_pos + footer.len()
+            } else if let Some(next_pos) = self.next_image_start(data, start_pos, positions, current_index) {
+                // 使用下一个图片的开始位置
+                next_pos
+            } else {
+                // 如果是最后一张图片，使用合理的最大大小（1MB）
+                std::cmp::min(start_pos + 1024 * 1024, data.len())
             }
-        } else if current_index + 1 < all_positions.len() {
-            // 使用下一个图片的开始位置
-            all_positions[current_index + 1].0
         } else {
-            // 如果是最后一张图片，使用合理的最大大小（1MB）
-            std::cmp::min(start_pos + 1024 * 1024, data.len())
+            return Err(ExtractionError::ImageParse(format!("无法解析{}图片结构", signature.image_type)).into());
         };
 
         if end_pos > start_pos && end_pos <= data.len() {
-            Ok(data[start_pos..end_pos].to_vec())
+            Ok(SingleImage {
+                data: Cow::Borrowed(&data[start_pos..end_pos]),
+                image_type: format!("image/{}", span.and_then(|span| span.image_type).unwrap_or(signature.image_type)),
+                position: start_pos,
+                end_position: end_pos,
+                integrity: span.map_or(Integrity::Unknown, |span| span.integrity),
+                declared_type: false,
+                frame_count: span.and_then(|span| span.frame_count),
+                content_location: None,
+                original_filename: None,
+            })
         } else {
             Err(ExtractionError::ImageParse("无效的图片数据范围".to_string()).into())
         }
     }
 
-    /// 保存单张图片
-    fn save_image(&self, output_dir: &Path, index: usize, image: &ImageInfo) -> Result<PathBuf> {
-        let extension = self.get_file_extension(&image.image_type);
-        let filename = format!("image_{:03}{}", index, extension);
-        let image_path = output_dir.join(filename);
+    /// `start_pos` 之后下一张图片的开始位置：带结束标记的格式（JPEG、PNG、GIF）的签名位置，
+    /// 或能通过结构解析的其他签名位置
+    ///
+    /// 没有结束标记的格式魔数很短（如ICO的 `00 00 01 00`、`BM`），经常出现在图片数据内部
+    /// （JFIF文件头中就有ICO魔数），未经解析时不能作为上一张图片的结束位置。
+    fn next_image_start(
+        &self,
+        data: &[u8],
+        start_pos: usize,
+        positions: &mut SignatureCursor,
+        current_index: usize,
+    ) -> Option<usize> {
+        (current_index + 1..)
+            .map_while(|i| positions.get(i))
+            .find(|&(pos, signature)| {
+                pos > start_pos && (signature.footer.is_some() || (signature.parse)(data, pos).is_some())
+            })
+            .map(|(pos, _)| pos)
+    }
 
-        fs::write(&image_path, &image.data)
-            .with_context(|| format!("写入图片文件失败: {}", image_path.display()))?;
+    /// 保存单张图片，`converted_to` 不为 `None` 时转换为该格式后保存
+    ///
+    /// 启用去重时，内容与之前保存过的图片相同的图片会被跳过或硬链接到首次保存的文件；
+    /// 启用相似图片检测时，图片归入相似组，`drop` 模式下跳过与之前图片相似的图片。
+    fn save_image(
+        &self,
+        output_dir: &Path,
+        source_stem: &str,
+        index: usize,
+        image: &ImageInfo,
+        converted_to: Option<ConvertFormat>,
+        names: &mut UniqueNames,
+    ) -> Result<SaveOutcome> {
+        let output_type = converted_to.map_or(image.image_type.as_str(), |format| format.mime_type());
+        let name = self.output_file_name(source_stem, index, image, output_type);
+        let mut image_path = output_dir.join(names.claim(&name));
+        match self.options.overwrite {
+            OverwritePolicy::Overwrite => {}
+            OverwritePolicy::Skip => {
+                if image_path.exists() {
+                    return Ok(SaveOutcome::Exists { path: image_path });
+                }
+            }
+            OverwritePolicy::Rename => {
+                while image_path.exists() {
+                    image_path = output_dir.join(names.claim(&name));
+                }
+            }
+        }
+
+        if self.options.dedup != DedupMode::Off {
+            if let Some(original) = self.dedup_index.check_or_insert(&image.hash, &image_path) {
+                if self.options.dedup == DedupMode::Skip {
+                    return Ok(SaveOutcome::Skipped { original });
+                }
+                if !self.options.dry_run {
+                    let data = self.output_data(image, converted_to)?;
+                    self.create_output_dir(output_dir)?;
+                    dedup::link_or_copy(&original, &image_path, &data)
+                        .with_context(|| format!("创建硬链接失败: {}", image_path.display()))?;
+                }
+                return Ok(SaveOutcome::Linked { path: image_path, original });
+            }
+        }
+
+        let similar_to = match (&self.near_dup_index, image.perceptual_hash) {
+            (Some(index), Some(perceptual_hash)) => index.assign(perceptual_hash, &image_path),
+            _ => None,
+        };
+        if let Some((original, distance)) = &similar_to {
+            if self.near_dup_index.as_ref().is_some_and(|index| index.action() == NearDupAction::Drop) {
+                return Ok(SaveOutcome::NearDuplicate { original: original.clone(), distance: *distance });
+            }
+        }
+
+        if !self.options.dry_run {
+            let data = self.output_data(image, converted_to)?;
+            self.create_output_dir(output_dir)?;
+            fs::write(&image_path, &data)
+                .with_context(|| format!("写入图片文件失败: {}", image_path.display()))?;
+        }
+
+        Ok(SaveOutcome::Saved { path: image_path, similar_to })
+    }
+
+    /// 在第一次实际写入文件前创建输出目录，所有图片都被跳过时不留下空目录
+    fn create_output_dir(&self, output_dir: &Path) -> Result<()> {
+        fs::create_dir_all(output_dir).with_context(|| format!("创建输出目录失败: {}", output_dir.display()))
+    }
+
+    /// 图片保存时重新编码成的格式：需要转换格式或缩小尺寸时返回目标格式，
+    /// 已是目标格式且不需要缩小、或无法解码的格式返回 `None`（按原始数据保存）
+    fn conversion_target(&self, image: &ImageInfo) -> Option<ConvertFormat> {
+        if !self.can_decode(&image.image_type) {
+            return None;
+        }
+        if self.resized_dimensions(image).is_some() {
+            return Some(
+                self.options
+                    .convert_to
+                    .unwrap_or_else(|| ConvertFormat::for_reencoding(&image.image_type)),
+            );
+        }
+        self.options
+            .convert_to
+            .filter(|format| format.mime_type() != image.image_type)
+    }
+
+    /// 按 `max_dimension` 缩小后的尺寸，不需要缩小、尺寸未知或无法解码时返回 `None`
+    fn resized_dimensions(&self, image: &ImageInfo) -> Option<(u32, u32)> {
+        let (width, height) = image.dimensions()?;
+        let max_dimension = self
+            .options
+            .max_dimension
+            .filter(|&max_dimension| width.max(height) > max_dimension)?;
+        self.can_decode(&image.image_type)
+            .then(|| convert::fit_within(width, height, max_dimension, max_dimension))
+    }
 
-        Ok(image_path)
+    /// 要写入的图片数据，需要转换格式或缩小尺寸时解码后重新编码
+    fn output_data<'d>(&self, image: &'d ImageInfo, converted_to: Option<ConvertFormat>) -> Result<Cow<'d, [u8]>> {
+        let Some(format) = converted_to else {
+            return Ok(Cow::Borrowed(&image.data));
+        };
+        let mut decoded = self
+            .try_decode_image(&image.data)
+            .with_context(|| format!("解码图片失败，无法转换为{}", format.mime_type()))?;
+        if let Some((width, height)) = self.resized_dimensions(image) {
+            decoded = convert::resize(decoded, width, height);
+        }
+        let data = convert::convert(&decoded, format, self.options.quality)
+            .with_context(|| format!("转换为{}失败", format.mime_type()))?;
+        Ok(Cow::Owned(data))
+    }
+
+    /// 在输出目录的缩略图子目录中写入不超过 `max_size` 的缩略图（预览时不写入），返回缩略图路径
+    ///
+    /// 缩略图与保存的图片同名（扩展名按缩略图格式），格式与保存的图片相同（PNG、JPEG、WebP以外的格式改为PNG）。
+    /// 无法解码的格式不生成缩略图；生成失败时输出警告，返回 `None`。
+    fn save_thumbnail(
+        &self,
+        image_path: &Path,
+        image: &ImageInfo,
+        converted_to: Option<ConvertFormat>,
+        (max_width, max_height): (u32, u32),
+        log: &mut ProgressLog,
+    ) -> Option<PathBuf> {
+        if !self.can_decode(&image.image_type) {
+            return None;
+        }
+        let format = converted_to.unwrap_or_else(|| ConvertFormat::for_reencoding(&image.image_type));
+        let file_name = image_path.file_name()?.to_string_lossy();
+        let thumbnail_dir = image_path.parent()?.join(THUMBNAIL_DIR);
+        let thumbnail_path = thumbnail_dir.join(naming::with_extension(
+            &file_name,
+            self.get_file_extension(format.mime_type()),
+            extension_aliases(format.mime_type()),
+        ));
+        if self.options.dry_run {
+            return Some(thumbnail_path);
+        }
+
+        let written = self
+            .try_decode_image(&image.data)
+            .context("解码图片失败")
+            .and_then(|decoded| {
+                let (width, height) = convert::fit_within(decoded.width(), decoded.height(), max_width, max_height);
+                convert::convert(&convert::resize(decoded, width, height), format, self.options.quality)
+                    .context("编码缩略图失败")
+            })
+            .and_then(|data| {
+                fs::create_dir_all(&thumbnail_dir)
+                    .with_context(|| format!("创建缩略图目录失败: {}", thumbnail_dir.display()))?;
+                fs::write(&thumbnail_path, data)
+                    .with_context(|| format!("写入缩略图失败: {}", thumbnail_path.display()))
+            });
+        match written {
+            Ok(()) => Some(thumbnail_path),
+            Err(e) => {
+                log.warn(format!("生成缩略图失败 ({}): {:#}", image_path.display(), e));
+                None
+            }
+        }
+    }
+
+    /// 把无法解码的图片和原因说明写入隔离目录（预览时不写入），返回隔离后的清单记录
+    ///
+    /// 写入失败时输出警告，返回原来的跳过记录。
+    fn quarantine_image(
+        &self,
+        source_file: &Path,
+        quarantine_dir: &Path,
+        entry: ManifestEntry,
+        data: &[u8],
+        names: &mut UniqueNames,
+        log: &mut ProgressLog,
+    ) -> ManifestEntry {
+        let extension = self.get_file_extension(&entry.image_type);
+        let image_path = quarantine_dir.join(names.claim(&format!("offset_{}{}", entry.position, extension)));
+        if !self.options.dry_run {
+            let mut reason_path = image_path.clone().into_os_string();
+            reason_path.push(".txt");
+            let reason = format!(
+                "源文件: {}\n位置: {}-{}\n类型: {}\n大小: {} bytes\n完整性: {}\n原因: {}\n错误: {}\n修复: {}\n",
+                source_file.display(),
+                entry.position,
+                entry.end_position,
+                entry.image_type,
+                entry.size,
+                entry.integrity.label(),
+                entry.skip_reason.map_or("", |reason| reason.label()),
+                entry.error.as_deref().unwrap_or(""),
+                if self.options.repair { "未能修复" } else { "未尝试" },
+            );
+            let written = fs::create_dir_all(quarantine_dir)
+                .with_context(|| format!("创建隔离目录失败: {}", quarantine_dir.display()))
+                .and_then(|_| {
+                    fs::write(&image_path, data)
+                        .with_context(|| format!("写入隔离文件失败: {}", image_path.display()))
+                })
+                .and_then(|_| {
+                    fs::write(&reason_path, reason)
+                        .with_context(|| format!("写入隔离原因失败: {}", Path::new(&reason_path).display()))
+                });
+            if let Err(e) = written {
+                log.warn(format!("隔离图片失败 (偏移 {}): {:#}", entry.position, e));
+                return entry;
+            }
+        }
+
+        let verb = if self.options.dry_run { "将" } else { "已" };
+        log.info(format!("{}隔离无法解码的图片: {}", verb, image_path.display()));
+        ManifestEntry {
+            status: ImageStatus::Quarantined,
+            output_path: Some(image_path),
+            ..entry
+        }
+    }
+
+    /// 确定输出文件名，扩展名按输出的MIME类型 `output_type`（转换后的格式）确定
+    ///
+    /// 启用原始文件名时优先使用 `Content-Disposition` 文件名，其次是 `Content-Location` 的URL文件名；
+    /// 未启用或没有可用原名时按命名模板命名。
+    fn output_file_name(&self, source_stem: &str, index: usize, image: &ImageInfo, output_type: &str) -> String {
+        let extension = self.get_file_extension(output_type);
+        if self.options.keep_original_names {
+            let original = image
+                .original_filename
+                .as_deref()
+                // 部分工具会在文件名中带上客户端路径
+                .and_then(|name| name.rsplit(['/', '\\']).next())
+                .and_then(naming::sanitize_file_name)
+                .or_else(|| {
+                    image
+                        .content_location
+                        .as_deref()
+                        .and_then(naming::name_from_location)
+                        .and_then(|name| naming::sanitize_file_name(&name))
+                });
+            if let Some(name) = original {
+                let name = if output_type == image.image_type {
+                    name.as_str()
+                } else {
+                    naming::strip_extension(&name, extension_aliases(&image.image_type))
+                };
+                return naming::with_extension(name, extension, extension_aliases(output_type));
+            }
+        }
+        let rendered = self.options.name_template.render(source_stem, index, image, extension);
+        match naming::sanitize_file_name(&rendered) {
+            // 模板中没有 `{ext}` 时补上扩展名
+            Some(name) => naming::with_extension(&name, extension, extension_aliases(output_type)),
+            None => format!("image_{:03}{}", index, extension),
+        }
+    }
+
+    /// 图片格式是否在 `formats` 选项中（未指定格式时全部保留）
+    ///
+    /// 选项中的格式可以是MIME子类型（如 `jpeg`、`x-icon`）或扩展名（如 `jpg`、`ico`），不区分大小写。
+    fn format_selected(&self, image_type: &str) -> bool {
+        self.options.formats.is_empty() || self.options.formats.iter().any(|format| format_matches(image_type, format))
     }
 
     /// 在字节数组中查找子序列
@@ -291,19 +1077,16 @@ impl UHTMLImageExtractor {
             return None;
         }
 
-        data[start..]
-            .windows(pattern.len())
-            .position(|window| window == pattern)
-            .map(|pos| start + pos)
+        memmem::find(&data[start..], pattern).map(|pos| start + pos)
     }
 
-    /// 获取图片尺寸
-    fn get_image_dimensions(&self, image_data: &[u8]) -> Result<(u32, u32)> {
+    /// 获取图片尺寸（只读取文件头），无法解析时返回 `None`
+    fn get_image_dimensions(&self, image_data: &[u8]) -> Option<(u32, u32)> {
         let cursor = Cursor::new(image_data);
         match ImageReader::new(cursor).with_guessed_format() {
             Ok(reader) => {
                 match reader.into_dimensions() {
-                    Ok((width, height)) => Ok((width, height)),
+                    Ok((width, height)) => Some((width, height)),
                     Err(_) => {
                         // 如果无法解析尺寸，使用简单的方法解析常见格式
                         self.parse_image_dimensions_manually(image_data)
@@ -317,35 +1100,50 @@ impl UHTMLImageExtractor {
         }
     }
 
+    /// 完整解码图片，无法识别或解码失败时返回 `None`
+    fn decode_image(&self, image_data: &[u8]) -> Option<DynamicImage> {
+        self.try_decode_image(image_data).ok()
+    }
+
+    /// 完整解码图片，失败时返回解码错误
+    fn try_decode_image(&self, image_data: &[u8]) -> Result<DynamicImage, ImageError> {
+        ImageReader::new(Cursor::new(image_data))
+            .with_guessed_format()?
+            .decode()
+    }
+
+    /// `image` 库能否解码该MIME类型的图片（AVIF、HEIC等格式不支持解码）
+    fn can_decode(&self, image_type: &str) -> bool {
+        ImageFormat::from_mime_type(image_type).is_some_and(|format| format.reading_enabled())
+    }
+
     /// 手动解析图片尺寸（简单实现）
-    fn parse_image_dimensions_manually(&self, data: &[u8]) -> Result<(u32, u32)> {
+    fn parse_image_dimensions_manually(&self, data: &[u8]) -> Option<(u32, u32)> {
         if data.len() < 10 {
-            return Ok((0, 0));
+            return None;
         }
 
         // JPEG解析
         if data.starts_with(b"\xFF\xD8\xFF") {
-            if let Some((w, h)) = self.parse_jpeg_dimensions(data) {
-                return Ok((w, h));
-            }
+            return self.parse_jpeg_dimensions(data);
         }
 
         // PNG解析
         if data.starts_with(b"\x89PNG\r\n\x1a\n") {
-            if let Some((w, h)) = self.parse_png_dimensions(data) {
-                return Ok((w, h));
-            }
+            return self.parse_png_dimensions(data);
         }
 
         // GIF解析
         if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
-            if let Some((w, h)) = self.parse_gif_dimensions(data) {
-                return Ok((w, h));
-            }
+            return self.parse_gif_dimensions(data);
         }
 
-        // 如果无法解析，返回默认值
-        Ok((100, 100))  // 假设是有效图片
+        // ISO-BMFF (AVIF/HEIC) 解析
+        if data.get(4..8) == Some(b"ftyp") {
+            return self.parse_isobmff_dimensions(data);
+        }
+
+        None
     }
 
     /// 解析JPEG尺寸
@@ -354,7 +1152,7 @@ impl UHTMLImageExtractor {
         while pos + 4 < data.len() {
             if data[pos] == 0xFF {
                 let marker = data[pos + 1];
-                if marker >= 0xC0 && marker <= 0xC3 {
+                if (0xC0..=0xC3).contains(&marker) {
                     if pos + 9 < data.len() {
                         let height = u16::from_be_bytes([data[pos + 5], data[pos + 6]]) as u32;
                         let width = u16::from_be_bytes([data[pos + 7], data[pos + 8]]) as u32;
@@ -393,6 +1191,25 @@ impl UHTMLImageExtractor {
         }
     }
 
+    /// 解析ISO-BMFF尺寸（取面积最大的 `ispe` 属性，缩略图的尺寸更小）
+    fn parse_isobmff_dimensions(&self, data: &[u8]) -> Option<(u32, u32)> {
+        let mut largest: Option<(u32, u32)> = None;
+        let mut pos = 0;
+        // ispe: 长度(4) 类型(4) 版本和标志(4) 宽(4) 高(4)
+        while let Some(found_pos) = self.find_bytes(data, b"ispe", pos) {
+            if let Some(fields) = data.get(found_pos + 8..found_pos + 16) {
+                let width = u32::from_be_bytes([fields[0], fields[1], fields[2], fields[3]]);
+                let height = u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]);
+                let area = width as u64 * height as u64;
+                if largest.is_none_or(|(w, h)| area > w as u64 * h as u64) {
+                    largest = Some((width, height));
+                }
+            }
+            pos = found_pos + 1;
+        }
+        largest
+    }
+
     /// 根据MIME类型获取文件扩展名
     fn get_file_extension(&self, mime_type: &str) -> &'static str {
         match mime_type {
@@ -401,6 +1218,13 @@ impl UHTMLImageExtractor {
             "image/gif" => ".gif",
             "image/webp" => ".webp",
             "image/bmp" => ".bmp",
+            "image/tiff" => ".tiff",
+            "image/x-icon" => ".ico",
+            "image/x-cursor" => ".cur",
+            "image/avif" => ".avif",
+            "image/heic" => ".heic",
+            "image/heif" => ".heif",
+            "image/svg+xml" => ".svg",
             _ => ".img",
         }
     }
@@ -410,4 +1234,622 @@ impl Default for UHTMLImageExtractor {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+/// 按在源数据中的顺序逐张产出图片的迭代器
+///
+/// 由 [`UHTMLImageExtractor::images`] 创建。图片数据在迭代到时才解码和解析，
+/// 迭代器不保留已产出的图片；被格式或尺寸过滤掉的图片不会产出。
+/// 回退到签名扫描时也只向后扫描到下一张图片为止，取第一张图片不必先扫描完整个文件。
+pub struct ImageIter<'e, 'a> {
+    extractor: &'e UHTMLImageExtractor,
+    data: &'a [u8],
+    stage: Stage<'a>,
+    /// 已找到的候选图片数（包括被过滤的）
+    found: usize,
+    /// 已产出的图片数，即下一张图片的序号
+    yielded: usize,
+    log: ProgressLog,
+}
+
+/// 一张候选图片的过滤结果
+enum Examined<'a> {
+    /// 通过过滤的图片
+    Accepted(ImageInfo<'a>),
+    /// 被过滤掉的图片的清单记录；无法解码时附带图片数据，用于隔离
+    Rejected(ManifestEntry, Option<Cow<'a, [u8]>>),
+}
+
+impl Examined<'_> {
+    /// 不在源文件映射中、需要单独占用内存的数据量
+    fn owned_len(&self) -> usize {
+        let data = match self {
+            Examined::Accepted(image) => Some(&image.data),
+            Examined::Rejected(_, data) => data.as_ref(),
+        };
+        match data {
+            Some(Cow::Owned(data)) => data.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// 查找候选图片的阶段
+enum Stage<'a> {
+    /// 逐个处理MIME部分；`text` 为正在查找data URI的文本部分
+    Mime {
+        parts: std::vec::IntoIter<MimePart<'a>>,
+        text: Option<DataUriCursor<'a>>,
+    },
+    /// 按签名扫描原始字节
+    Scan(Box<SignatureScan<'a>>),
+    /// 没有更多图片
+    Done,
+}
+
+impl<'e, 'a> ImageIter<'e, 'a> {
+    /// 创建迭代器，提示信息写入 `log`
+    ///
+    /// 数据是MIME multipart容器时按各部分提取；不是有效容器或容器中找不到任何图片时，
+    /// 回退到按图片签名扫描原始字节。
+    fn new(extractor: &'e UHTMLImageExtractor, data: &'a [u8], log: ProgressLog) -> Self {
+        let stage = match mhtml::parse_multipart(data) {
+            Some(parts) => Stage::Mime { parts: parts.into_iter(), text: None },
+            None => Stage::Scan(Box::new(SignatureScan::new(data))),
+        };
+        Self {
+            extractor,
+            data,
+            stage,
+            found: 0,
+            yielded: 0,
+            log,
+        }
+    }
+
+    /// 提示信息
+    fn log(&mut self) -> &mut ProgressLog {
+        &mut self.log
+    }
+
+    /// 结束迭代，返回提示信息
+    fn finish(self) -> ProgressLog {
+        self.log
+    }
+
+    /// 查找下一张候选图片（未经过滤）
+    fn next_candidate(&mut self) -> Option<SingleImage<'a>> {
+        let extractor = self.extractor;
+        loop {
+            match &mut self.stage {
+                Stage::Mime { parts, text } => {
+                    if let Some(cursor) = text {
+                        if let Some(image) = cursor.next_image(extractor, &mut self.log) {
+                            return Some(image);
+                        }
+                        *text = None;
+                    }
+
+                    let Some(part) = parts.next() else {
+                        // 容器中找不到任何图片时回退到签名扫描
+                        self.stage = if self.found == 0 {
+                            Stage::Scan(Box::new(SignatureScan::new(self.data)))
+                        } else {
+                            Stage::Done
+                        };
+                        continue;
+                    };
+
+                    // 文本部分（HTML、CSS等）本身不是图片，但可能以data URI内联图片
+                    if !part.content_type.starts_with("image/") && part.content_type.starts_with("text/") {
+                        match part.decode_body() {
+                            Ok(body) => *text = Some(DataUriCursor::for_part(body, &part)),
+                            Err(e) => self
+                                .log
+                                .warn(format!("解码MIME部分失败 ({}): {}", extractor.describe_part(&part), e)),
+                        }
+                        continue;
+                    }
+
+                    if let Some(image) = extractor.mime_part_image(&part, &mut self.log) {
+                        return Some(image);
+                    }
+                }
+                Stage::Scan(scan) => match scan.next_image(extractor, self.data, &mut self.log) {
+                    Some(image) => return Some(image),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// 查找下一张候选图片并按选项过滤
+    fn next_examined(&mut self) -> Option<Examined<'a>> {
+        let extractor = self.extractor;
+        let options = &extractor.options;
+        let mut candidate = self.next_candidate()?;
+        self.found += 1;
+        let mut hash = dedup::content_hash(&candidate.data);
+
+        // 验证图片数据（最小大小检查）
+        if candidate.data.len() < 100 {  // 图片至少100字节
+            return Some(Examined::Rejected(candidate.skipped(&hash, SkipReason::TooFewBytes, None), None));
+        }
+
+        // 按字节数过滤
+        let size = candidate.data.len();
+        if let Some(min_bytes) = options.min_bytes.filter(|&min_bytes| size < min_bytes) {
+            self.log.info(format!("跳过图片: {} bytes (最小字节数: {})", size, min_bytes));
+            return Some(Examined::Rejected(candidate.skipped(&hash, SkipReason::BelowMinBytes, None), None));
+        }
+        if let Some(max_bytes) = options.max_bytes.filter(|&max_bytes| size > max_bytes) {
+            self.log.info(format!("跳过图片: {} bytes (最大字节数: {})", size, max_bytes));
+            return Some(Examined::Rejected(candidate.skipped(&hash, SkipReason::AboveMaxBytes, None), None));
+        }
+
+        // 只保留指定的格式
+        if !extractor.format_selected(&candidate.image_type) {
+            return Some(Examined::Rejected(candidate.skipped(&hash, SkipReason::FormatExcluded, None), None));
+        }
+
+        // 获取图片尺寸；校验、修复或隔离时完整解码，无法解码（且未能修复）的图片不输出
+        let mut decoded = None;
+        let mut repair = None;
+        let dimensions = if options.decode_check() && extractor.can_decode(&candidate.image_type) {
+            let image = match extractor.try_decode_image(&candidate.data) {
+                Ok(image) => image,
+                Err(e) => {
+                    let repaired = options
+                        .repair
+                        .then(|| repair::repair(&candidate.image_type, &candidate.data))
+                        .flatten()
+                        .and_then(|(data, method)| extractor.decode_image(&data).map(|image| (data, method, image)));
+                    let Some((data, method, image)) = repaired else {
+                        let action = if options.repair { "无法修复" } else { "无法解码" };
+                        self.log.info(format!("跳过{}的图片: 偏移 {} ({})", action, candidate.position, e));
+                        let entry = ManifestEntry {
+                            error: Some(e.to_string()),
+                            ..candidate.skipped(&hash, SkipReason::DecodeFailed, None)
+                        };
+                        return Some(Examined::Rejected(entry, Some(candidate.data)));
+                    };
+
+                    self.log.info(format!("已修复图片: 偏移 {} ({}, {})", candidate.position, method.label(), e));
+                    candidate.integrity = extractor
+                        .parse_image_structure(&data)
+                        .map_or(Integrity::Unknown, |(_, span)| span.integrity);
+                    hash = dedup::content_hash(&data);
+                    candidate.data = Cow::Owned(data);
+                    repair = Some(method);
+                    image
+                }
+            };
+            let dimensions = (image.width(), image.height());
+            decoded = Some(image);
+            Some(dimensions)
+        } else {
+            extractor.get_image_dimensions(&candidate.data)
+        };
+
+        // 类型已声明（如SVG）或结构完整的图片尺寸未知时不参与默认的最小尺寸过滤；
+        // 校验模式下，或明确指定了最大尺寸、宽高比时无法判断是否符合，跳过；
+        // 签名扫描猜测出的、结构也无法解析的数据段多半不是图片，一律跳过
+        let Some((width, height)) = dimensions else {
+            let trusted = candidate.declared_type || candidate.integrity != Integrity::Unknown;
+            let dimension_filtered = options.max_size.is_some() || options.aspect.is_some();
+            if options.validate || !trusted || dimension_filtered {
+                self.log.info(format!("跳过无法解析尺寸的图片: 偏移 {}", candidate.position));
+                return Some(Examined::Rejected(candidate.skipped(&hash, SkipReason::UnknownDimensions, None), None));
+            }
+            return Some(Examined::Accepted(self.accept(candidate, hash, None, repair, None)));
+        };
+
+        // 如果不是输出全部，过滤小于指定尺寸的图片
+        let (min_width, min_height) = options.min_size;
+        if !options.output_all && options.size_match.exceeds(width < min_width, height < min_height) {
+            self.log.info(format!("跳过小图片: {}x{} 像素 (最小尺寸: {}x{})", width, height, min_width, min_height));
+            let entry = candidate.skipped(&hash, SkipReason::BelowMinSize, Some((width, height)));
+            return Some(Examined::Rejected(entry, None));
+        }
+
+        // 过滤大于最大尺寸的图片
+        if let Some((max_width, max_height)) = options.max_size {
+            if options.size_match.exceeds(width > max_width, height > max_height) {
+                self.log.info(format!("跳过大图片: {}x{} 像素 (最大尺寸: {}x{})", width, height, max_width, max_height));
+                let entry = candidate.skipped(&hash, SkipReason::AboveMaxSize, Some((width, height)));
+                return Some(Examined::Rejected(entry, None));
+            }
+        }
+
+        // 过滤宽高比不在范围内的图片
+        if let Some(aspect) = options.aspect.as_ref().filter(|aspect| !aspect.contains(width, height)) {
+            self.log.info(format!("跳过图片: {}x{} 像素 (宽高比范围: {})", width, height, aspect));
+            let entry = candidate.skipped(&hash, SkipReason::AspectExcluded, Some((width, height)));
+            return Some(Examined::Rejected(entry, None));
+        }
+
+        Some(Examined::Accepted(self.accept(candidate, hash, Some((width, height)), repair, decoded)))
+    }
+
+    /// 接受通过过滤的图片，分配序号；启用相似图片检测时计算感知哈希（可复用已解码的图片）
+    fn accept(
+        &mut self,
+        candidate: SingleImage<'a>,
+        hash: String,
+        dimensions: Option<(u32, u32)>,
+        repair: Option<Repair>,
+        decoded: Option<DynamicImage>,
+    ) -> ImageInfo<'a> {
+        let perceptual_hash = match self.extractor.near_dup_index {
+            Some(_) => decoded
+                .or_else(|| self.extractor.decode_image(&candidate.data))
+                .map(|decoded| phash::dhash(&decoded)),
+            None => None,
+        };
+        let image = ImageInfo {
+            index: self.yielded,
+            image_type: candidate.image_type,
+            size: candidate.data.len(),
+            data: candidate.data,
+            position: candidate.position,
+            end_position: candidate.end_position,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            integrity: candidate.integrity,
+            repair,
+            frame_count: candidate.frame_count,
+            content_location: candidate.content_location,
+            original_filename: candidate.original_filename,
+            hash,
+            perceptual_hash,
+        };
+        self.yielded += 1;
+        image
+    }
+}
+
+impl<'a> Iterator for ImageIter<'_, 'a> {
+    type Item = ImageInfo<'a>;
+
+    fn next(&mut self) -> Option<ImageInfo<'a>> {
+        loop {
+            if let Examined::Accepted(image) = self.next_examined()? {
+                return Some(image);
+            }
+        }
+    }
+}
+
+/// 在一段文本中逐个查找并解码data URI图片
+struct DataUriCursor<'a> {
+    text: Cow<'a, [u8]>,
+    /// 下一次查找的起始位置
+    pos: usize,
+    /// 文本在UHTML数据中的起始位置
+    base: usize,
+    /// 文本经过传输编码时无法换算出精确位置，使用整个MIME部分的范围
+    part_range: Option<(usize, usize)>,
+}
+
+impl<'a> DataUriCursor<'a> {
+    /// 在未编码的原始数据中查找
+    fn raw(data: &'a [u8]) -> Self {
+        Self {
+            text: Cow::Borrowed(data),
+            pos: 0,
+            base: 0,
+            part_range: None,
+        }
+    }
+
+    /// 在解码后的MIME文本部分中查找
+    fn for_part(text: Cow<'a, [u8]>, part: &MimePart) -> Self {
+        let part_range = (!part.is_identity_encoded()).then(|| (part.offset, part.offset + part.raw_body.len()));
+        Self {
+            text,
+            pos: 0,
+            base: part.offset,
+            part_range,
+        }
+    }
+
+    fn next_image(&mut self, extractor: &UHTMLImageExtractor, log: &mut ProgressLog) -> Option<SingleImage<'static>> {
+        while let Some(uri) = datauri::next_image_data_uri(&self.text, &mut self.pos) {
+            let (position, end_position) = self
+                .part_range
+                .unwrap_or((self.base + uri.offset, self.base + uri.end));
+            if let Some(image) = extractor.data_uri_image(&uri, position, end_position, log) {
+                return Some(image);
+            }
+        }
+        None
+    }
+}
+
+/// 按签名扫描原始字节的进度
+///
+/// 签名图片与原始字节中的data URI图片按位置合并产出，位置相同时签名图片在前。
+struct SignatureScan<'a> {
+    /// 签名位置，按需向后扫描
+    positions: SignatureCursor<'a>,
+    /// 下一个要处理的签名位置
+    next: usize,
+    /// 上一张结构完整的图片的结束位置，位于其内部的签名（如JPEG中嵌入的EXIF缩略图）会被跳过
+    covered_until: usize,
+    uris: DataUriCursor<'a>,
+    /// 已找到、等待按位置合并的签名图片和data URI图片
+    pending_signature: Option<SingleImage<'a>>,
+    pending_uri: Option<SingleImage<'static>>,
+}
+
+impl<'a> SignatureScan<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            positions: SignatureCursor::new(data),
+            next: 0,
+            covered_until: 0,
+            uris: DataUriCursor::raw(data),
+            pending_signature: None,
+            pending_uri: None,
+        }
+    }
+
+    fn next_image(
+        &mut self,
+        extractor: &UHTMLImageExtractor,
+        data: &'a [u8],
+        log: &mut ProgressLog,
+    ) -> Option<SingleImage<'a>> {
+        if self.pending_signature.is_none() {
+            self.pending_signature = self.next_signature_image(extractor, data);
+        }
+        if self.pending_uri.is_none() {
+            self.pending_uri = self.uris.next_image(extractor, log);
+        }
+        match (&self.pending_signature, &self.pending_uri) {
+            (Some(signature_image), Some(uri_image)) if uri_image.position < signature_image.position => {
+                self.pending_uri.take()
+            }
+            (Some(_), _) => self.pending_signature.take(),
+            (None, _) => self.pending_uri.take(),
+        }
+    }
+
+    fn next_signature_image(&mut self, extractor: &UHTMLImageExtractor, data: &'a [u8]) -> Option<SingleImage<'a>> {
+        while let Some((pos, signature)) = self.positions.get(self.next) {
+            let i = self.next;
+            self.next += 1;
+            if pos < self.covered_until {
+                continue;
+            }
+
+            if let Ok(single) = extractor.extract_single_image(data, pos, signature, &mut self.positions, i) {
+                if single.integrity != Integrity::Unknown {
+                    self.covered_until = single.end_position;
+                }
+                return Some(single);
+            }
+        }
+        None
+    }
+}
+
+/// 可识别的图片MIME类型，即 `formats` 过滤可以选择的格式
+const KNOWN_IMAGE_TYPES: &[&str] = &[
+    "image/jpeg", "image/png", "image/gif", "image/webp", "image/bmp", "image/tiff", "image/x-icon",
+    "image/x-cursor", "image/avif", "image/heic", "image/heif", "image/svg+xml",
+];
+
+/// 与MIME类型对应的可接受扩展名（不含点，小写）
+fn extension_aliases(mime_type: &str) -> &'static [&'static str] {
+    match mime_type {
+        "image/jpeg" => &["jpg", "jpeg", "jpe", "jfif"],
+        "image/png" => &["png"],
+        "image/gif" => &["gif"],
+        "image/webp" => &["webp"],
+        "image/bmp" => &["bmp", "dib"],
+        "image/tiff" => &["tif", "tiff"],
+        "image/x-icon" => &["ico"],
+        "image/x-cursor" => &["cur"],
+        "image/avif" => &["avif"],
+        "image/heic" => &["heic"],
+        "image/heif" => &["heif", "heic"],
+        "image/svg+xml" => &["svg"],
+        _ => &[],
+    }
+}
+
+/// 格式名（MIME子类型或扩展名，不区分大小写，可带前导点）是否表示该MIME类型
+fn format_matches(image_type: &str, format: &str) -> bool {
+    let format = format.trim().trim_start_matches('.').to_ascii_lowercase();
+    let subtype = image_type.strip_prefix("image/").unwrap_or(image_type);
+    format == subtype || extension_aliases(image_type).contains(&format.as_str())
+}
+
+/// 格式名能否用于 `formats` 过滤：已知格式的MIME子类型（如 `jpeg`、`svg+xml`）或扩展名（如 `jpg`、`svg`）
+pub fn is_known_format(format: &str) -> bool {
+    KNOWN_IMAGE_TYPES.iter().any(|image_type| format_matches(image_type, format))
+}
+
+/// 用于提示信息的尺寸，如 `800x600`，未知时为 `尺寸未知`
+fn format_dimensions(dimensions: Option<(u32, u32)>) -> String {
+    dimensions.map_or_else(|| "尺寸未知".to_string(), |(width, height)| format!("{}x{}", width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, RgbImage};
+
+    use super::*;
+    use crate::options::AspectRange;
+
+    /// 160x120 的测试图片，尺寸满足默认的最小尺寸过滤
+    fn encode(format: ImageOutputFormat) -> Vec<u8> {
+        let image = RgbImage::from_fn(160, 120, |x, y| image::Rgb([(x * 2) as u8, (y * 2) as u8, (x ^ y) as u8]));
+        let mut encoded = Cursor::new(Vec::new());
+        image.write_to(&mut encoded, format).unwrap();
+        encoded.into_inner()
+    }
+
+    /// 直接内嵌一张图片数据的页面
+    fn page_with(image: &[u8]) -> Vec<u8> {
+        let mut page = b"<html>".to_vec();
+        page.extend_from_slice(image);
+        page
+    }
+
+    #[test]
+    fn truncated_jfif_is_not_cut_at_magic_in_header() {
+        let jpeg = encode(ImageOutputFormat::Jpeg(85));
+        // JFIF文件头中像素比为1:1时，偏移13处正好是ICO魔数
+        assert_eq!(&jpeg[6..11], b"JFIF\0");
+        assert_eq!(&jpeg[13..17], b"\0\0\x01\0");
+
+        let mut data = b"<html>zz".to_vec();
+        let start = data.len();
+        data.extend_from_slice(&jpeg[..jpeg.len() * 2 / 3]);
+
+        let extractor = UHTMLImageExtractor::with_options(ExtractOptions::new().with_output_all(true));
+        let images = extractor.extract_images_from_bytes(&data).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].image_type, "image/jpeg");
+        assert_eq!(images[0].position, start);
+        assert_eq!(images[0].end_position, data.len());
+        assert_eq!(images[0].dimensions(), Some((160, 120)));
+    }
+
+    #[test]
+    fn truncated_png_ends_at_next_jpeg() {
+        let png = encode(ImageOutputFormat::Png);
+        let jpeg = encode(ImageOutputFormat::Jpeg(85));
+
+        let mut data = png[..png.len() * 2 / 3].to_vec();
+        data.extend_from_slice(b"zz");
+        let jpeg_start = data.len();
+        data.extend_from_slice(&jpeg[..jpeg.len() * 2 / 3]);
+
+        let extractor = UHTMLImageExtractor::with_options(ExtractOptions::new().with_output_all(true));
+        let images = extractor.extract_images_from_bytes(&data).unwrap();
+        let ranges: Vec<_> = images
+            .iter()
+            .map(|image| (image.image_type.as_str(), image.position, image.end_position))
+            .collect();
+        assert_eq!(ranges, [("image/png", 0, jpeg_start), ("image/jpeg", jpeg_start, data.len())]);
+    }
+
+    fn svg_page() -> Vec<u8> {
+        use base64::Engine;
+
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="64" height="64"><rect width="64" height="64" fill="red"/></svg>"#;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(svg);
+        format!("<html><img src=\"data:image/svg+xml;base64,{}\"></html>", encoded).into_bytes()
+    }
+
+    #[test]
+    fn svg_without_dimensions_is_kept() {
+        let data = svg_page();
+        let images = UHTMLImageExtractor::new().extract_images_from_bytes(&data).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].image_type, "image/svg+xml");
+        assert_eq!(images[0].dimensions(), None);
+
+        let manifest = ManifestEntry::for_image(&images[0], ImageStatus::Saved);
+        assert_eq!((manifest.width, manifest.height), (None, None));
+    }
+
+    #[test]
+    fn scanned_bytes_without_dimensions_are_skipped() {
+        let mut data = b"<html>\xFF\xD8\xFF".to_vec();
+        data.extend_from_slice(&[b'A'; 400]);
+        data.extend_from_slice(b"\xFF\xD9");
+
+        let extractor = UHTMLImageExtractor::with_options(ExtractOptions::new().with_output_all(true));
+        assert!(extractor.extract_images_from_bytes(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn dimension_filters_skip_images_without_dimensions() {
+        let data = svg_page();
+        let aspect = AspectRange::parse("1-").unwrap();
+        for options in [ExtractOptions::new().with_max_size(1000, 1000), ExtractOptions::new().with_aspect(aspect)] {
+            let extractor = UHTMLImageExtractor::with_options(options);
+            assert!(extractor.extract_images_from_bytes(&data).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn known_format_names() {
+        for format in ["jpeg", "JPG", ".png", "svg", "svg+xml", "x-icon", "ico", "tif", "heic"] {
+            assert!(is_known_format(format), "{}", format);
+        }
+        for format in ["jepg", "", "image/png", "webm", "*"] {
+            assert!(!is_known_format(format), "{}", format);
+        }
+    }
+
+    #[test]
+    fn template_without_ext_gets_extension() {
+        let data = svg_page();
+        let image = &UHTMLImageExtractor::new().extract_images_from_bytes(&data).unwrap()[0];
+        for (template, expected) in [
+            ("{source_stem}_{index}", "page_001.svg"),
+            ("{source_stem}.v2_{index}", "page.v2_001.svg"),
+            ("{source_stem}_{index}.{ext}", "page_001.svg"),
+            ("{source_stem}_{index}.SVG", "page_001.SVG"),
+        ] {
+            let options = ExtractOptions::new().with_name_template(naming::NameTemplate::parse(template).unwrap());
+            let extractor = UHTMLImageExtractor::with_options(options);
+            assert_eq!(extractor.output_file_name("page", 1, image, "image/svg+xml"), expected, "{}", template);
+        }
+    }
+
+    #[test]
+    fn validate_skips_images_without_dimensions() {
+        let data = svg_page();
+        let extractor = UHTMLImageExtractor::with_options(ExtractOptions::new().with_validate(true));
+        assert!(extractor.extract_images_from_bytes(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn skipped_duplicates_leave_no_empty_output_directory() {
+        let page = page_with(&encode(ImageOutputFormat::Png));
+
+        let root = std::env::temp_dir().join(format!("uhtml-pics-parse-dedup-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let source = root.join("page.uhtml");
+        fs::write(&source, &page).unwrap();
+        let (first, second) = (root.join("first"), root.join("second"));
+
+        let extractor = UHTMLImageExtractor::with_options(ExtractOptions::new().with_dedup(DedupMode::Skip).with_quiet(true));
+        let result = extractor.extract_images_from_file(&source, Some(&first)).unwrap();
+        assert_eq!(result.saved_images, 1);
+        let result = extractor.extract_images_from_file(&source, Some(&second)).unwrap();
+        assert_eq!(result.duplicate_images, 1);
+        assert!(first.is_dir());
+        assert!(!second.exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn parallel_dedup_keeps_images_of_first_file() {
+        let page = page_with(&encode(ImageOutputFormat::Png));
+
+        let root = std::env::temp_dir().join(format!("uhtml-pics-parse-jobs-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        for i in 0..8 {
+            fs::write(root.join(format!("page{}.uhtml", i)), &page).unwrap();
+        }
+
+        let options = ExtractOptions::new().with_dedup(DedupMode::Skip).with_jobs(4).with_quiet(true);
+        let results = UHTMLImageExtractor::with_options(options).extract_images_from_directory(&root, false).unwrap();
+        let sources: Vec<_> = results.iter().map(|result| result.source_file.clone()).collect();
+        assert_eq!(sources, (0..8).map(|i| root.join(format!("page{}.uhtml", i))).collect::<Vec<_>>());
+        assert_eq!(results[0].saved_images, 1);
+        assert!(results[1..].iter().all(|result| result.saved_images == 0 && result.duplicate_images == 1));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
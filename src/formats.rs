@@ -0,0 +1,636 @@
+//! 图片格式结构解析
+//!
+//! 按各格式自身的结构（标记段、数据块、长度字段）确定图片在数据中的真实边界，
+//! 而不是依赖第一次出现的结束标记。
+
+use serde::Serialize;
+
+/// 图片数据完整性判定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrity {
+    /// 结构完整，校验通过
+    Valid,
+    /// 结构可以解析，但存在校验错误（如PNG块CRC不匹配）
+    Damaged,
+    /// 未能按结构解析，边界为估计值
+    Unknown,
+}
+
+impl Integrity {
+    /// 用于输出的中文描述
+    pub fn label(&self) -> &'static str {
+        match self {
+            Integrity::Valid => "完整",
+            Integrity::Damaged => "已损坏",
+            Integrity::Unknown => "未校验",
+        }
+    }
+}
+
+/// 结构解析得到的图片范围
+#[derive(Debug, Clone, Copy)]
+pub struct ImageSpan {
+    /// 图片结束位置（不含）
+    pub end: usize,
+    pub integrity: Integrity,
+    /// 帧数（仅对可能包含多帧的格式解析，如GIF）
+    pub frame_count: Option<u32>,
+    /// 解析时确定的具体格式，覆盖签名表中的类型（如ISO-BMFF容器中的avif/heic）
+    pub image_type: Option<&'static str>,
+}
+
+impl ImageSpan {
+    fn valid(end: usize) -> Self {
+        Self {
+            end,
+            integrity: Integrity::Valid,
+            frame_count: None,
+            image_type: None,
+        }
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// 遍历JPEG标记段，找到真实的EOI
+///
+/// 依次跳过带长度的段（APPn、DQT、SOFn等），在SOS之后扫描熵编码数据
+/// （跳过 `FF 00` 填充字节和RST标记），直到遇到不属于任何嵌入数据的EOI。
+/// 这样EXIF中嵌入的缩略图（自带SOI/EOI）不会提前截断原图。
+/// 数据不完整或结构无效时返回 `None`。
+pub fn jpeg_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if data.get(start..start + 2)? != b"\xFF\xD8" {
+        return None;
+    }
+
+    let mut pos = start + 2;
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+        // 标记前允许任意数量的0xFF填充
+        while data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // EOI
+            0xD9 => return Some(ImageSpan::valid(pos + 2)),
+            // RSTn 和 TEM 不带长度
+            0xD0..=0xD7 | 0x01 => pos += 2,
+            // 段之间不应出现SOI或填充字节
+            0xD8 | 0x00 => return None,
+            _ => {
+                let length = be_u16(data, pos + 2)? as usize;
+                if length < 2 {
+                    return None;
+                }
+                pos += 2 + length;
+                if pos > data.len() {
+                    return None;
+                }
+                // SOS之后是熵编码数据，需要扫描到下一个真实标记
+                if marker == 0xDA {
+                    pos = skip_entropy_coded_data(data, pos)?;
+                }
+            }
+        }
+    }
+}
+
+/// 跳过熵编码数据，返回下一个标记（非RST）的位置
+fn skip_entropy_coded_data(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        pos += data.get(pos..)?.iter().position(|&b| b == 0xFF)?;
+        match *data.get(pos + 1)? {
+            // 填充字节和RST标记属于扫描数据的一部分
+            0x00 | 0xD0..=0xD7 => pos += 2,
+            // 连续的0xFF是标记前的填充
+            0xFF => pos += 1,
+            _ => return Some(pos),
+        }
+    }
+}
+
+/// 从签名开始遍历PNG数据块，校验每个块的CRC，在IEND处结束
+///
+/// 第一个块必须是IHDR。块长度越界或块类型不合法时视为结构无效并返回 `None`；
+/// 结构完整但存在CRC错误的图片标记为 [`Integrity::Damaged`]。
+pub fn png_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if data.get(start..start + PNG_SIGNATURE.len())? != PNG_SIGNATURE {
+        return None;
+    }
+
+    let mut pos = start + PNG_SIGNATURE.len();
+    let mut damaged = false;
+    let mut first_chunk = true;
+    loop {
+        let length = be_u32(data, pos)? as usize;
+        let chunk_type = data.get(pos + 4..pos + 8)?;
+        if !chunk_type.iter().all(|b| b.is_ascii_alphabetic()) {
+            return None;
+        }
+        if first_chunk && chunk_type != b"IHDR" {
+            return None;
+        }
+        first_chunk = false;
+
+        // 类型 + 数据 参与CRC计算
+        let crc_end = pos.checked_add(8)?.checked_add(length)?;
+        let crc_input = data.get(pos + 4..crc_end)?;
+        let stored_crc = be_u32(data, crc_end)?;
+        if crc32fast::hash(crc_input) != stored_crc {
+            damaged = true;
+        }
+
+        pos = crc_end + 4;
+        if chunk_type == b"IEND" {
+            return Some(ImageSpan {
+                integrity: if damaged { Integrity::Damaged } else { Integrity::Valid },
+                ..ImageSpan::valid(pos)
+            });
+        }
+    }
+}
+
+/// 解析GIF块结构，找到真实的结尾标记并统计帧数
+///
+/// 依次跳过逻辑屏幕描述符、全局/局部颜色表、扩展块和图像数据的子块链，
+/// 因此LZW数据中出现的 `00 3B` 不会被误认为结尾。
+pub fn gif_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    let header = data.get(start..start + 6)?;
+    if header != b"GIF87a" && header != b"GIF89a" {
+        return None;
+    }
+
+    // 逻辑屏幕描述符：宽(2) 高(2) 标志(1) 背景色(1) 像素宽高比(1)
+    let flags = *data.get(start + 10)?;
+    let mut pos = start + 13;
+    if flags & 0x80 != 0 {
+        pos += gif_color_table_size(flags);
+    }
+
+    let mut frame_count = 0;
+    loop {
+        match *data.get(pos)? {
+            // 图像描述符：位置(4) 尺寸(4) 标志(1)，之后是LZW最小码长和数据子块
+            0x2C => {
+                let image_flags = *data.get(pos + 9)?;
+                pos += 10;
+                if image_flags & 0x80 != 0 {
+                    pos += gif_color_table_size(image_flags);
+                }
+                data.get(pos)?;
+                pos = skip_gif_sub_blocks(data, pos + 1)?;
+                frame_count += 1;
+            }
+            // 扩展块：标签(1)，之后是数据子块
+            0x21 => {
+                data.get(pos + 1)?;
+                pos = skip_gif_sub_blocks(data, pos + 2)?;
+            }
+            // 结尾标记
+            0x3B => {
+                return Some(ImageSpan {
+                    frame_count: Some(frame_count),
+                    ..ImageSpan::valid(pos + 1)
+                });
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// 根据GIF标志字节计算颜色表的字节数
+fn gif_color_table_size(flags: u8) -> usize {
+    3 * (1 << ((flags & 0x07) + 1))
+}
+
+/// 跳过GIF数据子块链，返回块终止符之后的位置
+fn skip_gif_sub_blocks(data: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let size = *data.get(pos)? as usize;
+        pos += 1;
+        if size == 0 {
+            return Some(pos);
+        }
+        pos += size;
+    }
+}
+
+/// 按RIFF容器长度确定WebP边界，并检查内部数据块是否恰好填满容器
+pub fn webp_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if data.get(start..start + 4)? != b"RIFF" || data.get(start + 8..start + 12)? != b"WEBP" {
+        return None;
+    }
+
+    let riff_size = le_u32(data, start + 4)? as usize;
+    let end = start.checked_add(8)?.checked_add(riff_size)?;
+    if end > data.len() {
+        return None;
+    }
+
+    // 第一个数据块必须是 VP8 / VP8L / VP8X
+    if !matches!(data.get(start + 12..start + 16)?, b"VP8 " | b"VP8L" | b"VP8X") {
+        return None;
+    }
+
+    // 数据块：FourCC(4) 长度(4) 数据（按偶数字节对齐）
+    let mut pos = start + 12;
+    while pos + 8 <= end {
+        let chunk_size = le_u32(data, pos + 4)? as usize;
+        pos = pos.checked_add(8)?.checked_add(chunk_size + (chunk_size & 1))?;
+    }
+
+    Some(ImageSpan {
+        integrity: if pos == end { Integrity::Valid } else { Integrity::Damaged },
+        ..ImageSpan::valid(end)
+    })
+}
+
+/// 按BMP文件头中的文件大小确定边界
+///
+/// `BM` 签名很短，因此额外检查像素偏移、DIB头长度和色彩平面数，避免误判。
+pub fn bmp_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if data.get(start..start + 2)? != b"BM" {
+        return None;
+    }
+
+    let file_size = le_u32(data, start + 2)? as usize;
+    let reserved = le_u32(data, start + 6)?;
+    let pixel_offset = le_u32(data, start + 10)? as usize;
+    let dib_size = le_u32(data, start + 14)?;
+    if reserved != 0 || !matches!(dib_size, 12 | 40 | 52 | 56 | 64 | 108 | 124) {
+        return None;
+    }
+    if pixel_offset < 14 + dib_size as usize || pixel_offset >= file_size {
+        return None;
+    }
+
+    // BITMAPCOREHEADER 中尺寸为16位，其余为32位
+    let planes_pos = if dib_size == 12 { start + 22 } else { start + 26 };
+    if le_u16(data, planes_pos)? != 1 {
+        return None;
+    }
+
+    let end = start.checked_add(file_size)?;
+    if end > data.len() {
+        return None;
+    }
+    Some(ImageSpan::valid(end))
+}
+
+/// 遍历TIFF的IFD链，以所有条目数据和条带/分块数据的最远位置作为边界
+///
+/// TIFF没有总长度字段，必须包含条带或分块数据才视为图片
+/// （JPEG的EXIF段中也有TIFF头，但通常不含图像数据）。
+pub fn tiff_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    let little_endian = match data.get(start..start + 4)? {
+        b"II*\0" => true,
+        b"MM\0*" => false,
+        _ => return None,
+    };
+    let tiff = TiffReader {
+        data: data.get(start..)?,
+        little_endian,
+    };
+
+    let mut end = 8;
+    let mut has_image_data = false;
+    let mut ifd = tiff.u32(4)? as usize;
+    let mut visited = 0;
+    while ifd != 0 {
+        // 防止损坏的IFD链形成循环
+        visited += 1;
+        if ifd < 8 || visited > 64 {
+            return None;
+        }
+
+        let entry_count = tiff.u16(ifd)? as usize;
+        let next_ifd_pos = ifd + 2 + entry_count * 12;
+        end = end.max(next_ifd_pos + 4);
+
+        let mut offsets = Vec::new();
+        let mut byte_counts = Vec::new();
+        for i in 0..entry_count {
+            let entry = ifd + 2 + i * 12;
+            let tag = tiff.u16(entry)?;
+            let field_type = tiff.u16(entry + 2)?;
+            let count = tiff.u32(entry + 4)? as usize;
+
+            // 超过4字节的值存放在偏移处
+            let value_size = tiff_type_size(field_type).checked_mul(count)?;
+            let value_pos = if value_size > 4 {
+                let offset = tiff.u32(entry + 8)? as usize;
+                end = end.max(offset.checked_add(value_size)?);
+                offset
+            } else {
+                entry + 8
+            };
+
+            match tag {
+                // StripOffsets / TileOffsets
+                273 | 324 => offsets = tiff.values(value_pos, field_type, count)?,
+                // StripByteCounts / TileByteCounts
+                279 | 325 => byte_counts = tiff.values(value_pos, field_type, count)?,
+                _ => {}
+            }
+        }
+
+        if !offsets.is_empty() && offsets.len() == byte_counts.len() {
+            has_image_data = true;
+            for (offset, byte_count) in offsets.iter().zip(&byte_counts) {
+                end = end.max(offset.checked_add(*byte_count)?);
+            }
+        }
+
+        ifd = tiff.u32(next_ifd_pos)? as usize;
+    }
+
+    let end = start.checked_add(end)?;
+    if !has_image_data || end > data.len() {
+        return None;
+    }
+    Some(ImageSpan::valid(end))
+}
+
+/// 按字节序读取TIFF数据，偏移相对于TIFF头
+struct TiffReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl TiffReader<'_> {
+    fn u16(&self, pos: usize) -> Option<u16> {
+        if self.little_endian { le_u16(self.data, pos) } else { be_u16(self.data, pos) }
+    }
+
+    fn u32(&self, pos: usize) -> Option<u32> {
+        if self.little_endian { le_u32(self.data, pos) } else { be_u32(self.data, pos) }
+    }
+
+    /// 读取SHORT或LONG类型的数组
+    fn values(&self, pos: usize, field_type: u16, count: usize) -> Option<Vec<usize>> {
+        let size = tiff_type_size(field_type);
+        // 先确认整个数组都在数据范围内，避免按损坏的数量分配内存
+        self.data.get(pos..pos.checked_add(size.checked_mul(count)?)?)?;
+        (0..count)
+            .map(|i| match field_type {
+                3 => self.u16(pos + i * 2).map(usize::from),
+                4 => self.u32(pos + i * 4).map(|v| v as usize),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// TIFF字段类型对应的单个值字节数，未知类型为0
+fn tiff_type_size(field_type: u16) -> usize {
+    match field_type {
+        1 | 2 | 6 | 7 => 1,
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 0,
+    }
+}
+
+/// 按ICO/CUR目录项中的偏移和大小确定边界
+///
+/// 每个目录项指向的数据必须是PNG或BMP信息头（40字节），以排除签名误判。
+pub fn ico_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if le_u16(data, start)? != 0 || !matches!(le_u16(data, start + 2)?, 1 | 2) {
+        return None;
+    }
+
+    let count = le_u16(data, start + 4)? as usize;
+    if count == 0 {
+        return None;
+    }
+
+    let directory_end = 6 + count * 16;
+    let mut end = directory_end;
+    for i in 0..count {
+        let entry = start + 6 + i * 16;
+        // 保留字节必须为0
+        if *data.get(entry + 3)? != 0 {
+            return None;
+        }
+        let size = le_u32(data, entry + 8)? as usize;
+        let offset = le_u32(data, entry + 12)? as usize;
+        if size == 0 || offset < directory_end {
+            return None;
+        }
+
+        let image_start = start.checked_add(offset)?;
+        let image = data.get(image_start..image_start.checked_add(size)?)?;
+        if !image.starts_with(PNG_SIGNATURE) && le_u32(image, 0)? != 40 {
+            return None;
+        }
+        end = end.max(offset + size);
+    }
+
+    Some(ImageSpan::valid(start + end))
+}
+
+/// 遍历ISO-BMFF顶层盒子，确定AVIF/HEIC图片的边界
+///
+/// 首个盒子必须是 `ftyp`，且品牌表明是图片（而不是视频）；
+/// 遇到非顶层盒子类型时结束，并要求至少包含 `meta` 盒子。
+pub fn isobmff_span(data: &[u8], start: usize) -> Option<ImageSpan> {
+    if data.get(start + 4..start + 8)? != b"ftyp" {
+        return None;
+    }
+
+    let ftyp_size = be_u32(data, start)? as usize;
+    if ftyp_size < 16 {
+        return None;
+    }
+    // 主品牌(4) 次版本(4) 兼容品牌列表
+    let major_brand = data.get(start + 8..start + 12)?;
+    let compatible_brands = data.get(start + 16..start.checked_add(ftyp_size)?)?;
+    let mut brands = std::iter::once(major_brand).chain(compatible_brands.chunks_exact(4));
+    let image_type = if brands.clone().any(|b| matches!(b, b"avif" | b"avis")) {
+        "avif"
+    } else if brands.clone().any(|b| matches!(b, b"heic" | b"heix" | b"heim" | b"heis" | b"hevc" | b"hevx")) {
+        "heic"
+    } else if brands.any(|b| matches!(b, b"mif1" | b"msf1")) {
+        "heif"
+    } else {
+        return None;
+    };
+
+    let mut pos = start + ftyp_size;
+    let mut has_meta = false;
+    while let Some(box_type) = data.get(pos + 4..pos + 8) {
+        if !matches!(box_type, b"meta" | b"mdat" | b"moov" | b"free" | b"skip" | b"uuid" | b"idat") {
+            break;
+        }
+        has_meta |= box_type == b"meta";
+
+        // 长度为1表示使用64位长度，长度为0（延伸到文件末尾）在容器中无法界定
+        let box_size = match be_u32(data, pos)? {
+            0 => return None,
+            1 => usize::try_from(be_u64(data, pos + 8)?).ok()?,
+            size => size as usize,
+        };
+        if box_size < 8 {
+            return None;
+        }
+        pos = pos.checked_add(box_size)?;
+        if pos > data.len() {
+            return None;
+        }
+    }
+
+    if !has_meta {
+        return None;
+    }
+    Some(ImageSpan {
+        image_type: Some(image_type),
+        ..ImageSpan::valid(pos)
+    })
+}
+
+fn be_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], pos: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(pos..pos.checked_add(8)?)?.try_into().ok()?))
+}
+
+fn le_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(pos..pos.checked_add(2)?)?.try_into().ok()?))
+}
+
+fn le_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(pos..pos.checked_add(4)?)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageOutputFormat, RgbImage};
+
+    use super::*;
+
+    fn encode(format: ImageOutputFormat) -> Vec<u8> {
+        let image = RgbImage::from_fn(64, 48, |x, y| image::Rgb([(x * 4) as u8, (y * 4) as u8, (x ^ y) as u8]));
+        let mut encoded = Cursor::new(Vec::new());
+        image.write_to(&mut encoded, format).unwrap();
+        encoded.into_inner()
+    }
+
+    /// 一个带长度的JPEG标记段
+    fn jpeg_segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    #[test]
+    fn jpeg_exif_thumbnail_does_not_end_image() {
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend_from_slice(b"\xFF\xD8\xFF\xDB\x00\x03\x00\xFF\xD9");
+
+        let mut jpeg = b"\xFF\xD8".to_vec();
+        jpeg.extend_from_slice(&jpeg_segment(0xE1, &exif));
+        jpeg.extend_from_slice(&jpeg_segment(0xDA, &[1, 1, 0, 0, 0x3F, 0]));
+        // 熵编码数据中的填充字节和RST标记
+        jpeg.extend_from_slice(b"\x12\xFF\x00\x34\xFF\xD0\x56");
+        jpeg.extend_from_slice(b"\xFF\xD9");
+
+        let mut data = b"prefix".to_vec();
+        data.extend_from_slice(&jpeg);
+        data.extend_from_slice(b"\xFF\xD9trailing");
+
+        let span = jpeg_span(&data, 6).unwrap();
+        assert_eq!(span.end, 6 + jpeg.len());
+        assert_eq!(span.integrity, Integrity::Valid);
+    }
+
+    #[test]
+    fn jpeg_span_of_encoded_image() {
+        let jpeg = encode(ImageOutputFormat::Jpeg(85));
+        let span = jpeg_span(&jpeg, 0).unwrap();
+        assert_eq!(span.end, jpeg.len());
+    }
+
+    #[test]
+    fn truncated_jfif_has_no_span() {
+        let jpeg = encode(ImageOutputFormat::Jpeg(85));
+        assert_eq!(&jpeg[6..11], b"JFIF\0");
+        assert!(jpeg_span(&jpeg[..jpeg.len() * 2 / 3], 0).is_none());
+        assert!(jpeg_span(&jpeg[..20], 0).is_none());
+    }
+
+    #[test]
+    fn png_span_of_encoded_image() {
+        let png = encode(ImageOutputFormat::Png);
+        let mut data = png.clone();
+        data.extend_from_slice(b"trailing");
+
+        let span = png_span(&data, 0).unwrap();
+        assert_eq!(span.end, png.len());
+        assert_eq!(span.integrity, Integrity::Valid);
+    }
+
+    #[test]
+    fn png_with_bad_crc_is_damaged() {
+        let mut png = encode(ImageOutputFormat::Png);
+        // 修改IDAT数据中的一个字节，块结构不变，CRC不再匹配
+        let idat = png.windows(4).position(|window| window == b"IDAT").unwrap();
+        png[idat + 10] ^= 0xFF;
+
+        let span = png_span(&png, 0).unwrap();
+        assert_eq!(span.end, png.len());
+        assert_eq!(span.integrity, Integrity::Damaged);
+    }
+
+    #[test]
+    fn truncated_png_has_no_span() {
+        let png = encode(ImageOutputFormat::Png);
+        assert!(png_span(&png[..png.len() - 4], 0).is_none());
+    }
+
+    /// 1x1的GIF，每帧的LZW数据子块中都含有 `00 3B`
+    fn gif_with_trailer_bytes_in_lzw(frames: usize) -> Vec<u8> {
+        // 文件头，逻辑屏幕描述符（无全局颜色表）
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00".to_vec();
+        for _ in 0..frames {
+            // 图形控制扩展
+            gif.extend_from_slice(b"\x21\xF9\x04\x00\x0A\x00\x00\x00");
+            // 图像描述符，LZW最小码长，一个4字节的数据子块和块终止符
+            gif.extend_from_slice(b"\x2C\x00\x00\x00\x00\x01\x00\x01\x00\x00");
+            gif.extend_from_slice(b"\x02\x04\x00\x3B\x00\x3B\x00");
+        }
+        gif.push(0x3B);
+        gif
+    }
+
+    #[test]
+    fn gif_trailer_inside_lzw_data_is_skipped() {
+        let gif = gif_with_trailer_bytes_in_lzw(2);
+        let mut data = gif.clone();
+        data.extend_from_slice(b"\x00\x3Btrailing");
+
+        let span = gif_span(&data, 0).unwrap();
+        assert_eq!(span.end, gif.len());
+        assert_eq!(span.frame_count, Some(2));
+    }
+
+    #[test]
+    fn truncated_gif_has_no_span() {
+        let gif = gif_with_trailer_bytes_in_lzw(1);
+        assert!(gif_span(&gif[..gif.len() - 1], 0).is_none());
+    }
+}
@@ -0,0 +1,324 @@
+//! HTML图片总览
+//!
+//! 在每个源文件的输出目录中生成 `index.html`，并为整次运行生成一个总览页面，
+//! 以网格列出已保存的图片及其尺寸、大小、在源文件中的偏移和来源，不必逐个打开输出目录即可检查提取结果。
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::extractor::ExtractionResult;
+use crate::manifest::{ImageStatus, ManifestEntry};
+
+/// 每个输出目录中的总览页面文件名
+pub const GALLERY_FILE_NAME: &str = "index.html";
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 24px; color: #222; background: #fafafa; }
+h1 { font-size: 22px; }
+h2 { font-size: 17px; margin-top: 32px; word-break: break-all; }
+.summary { color: #555; }
+.error { color: #b00020; }
+.grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(200px, 1fr)); gap: 16px; }
+figure { margin: 0; padding: 8px; background: #fff; border: 1px solid #ddd; border-radius: 4px; }
+figure img { display: block; width: 100%; height: 160px; object-fit: contain; background: #eee; }
+figcaption { margin-top: 6px; font-size: 12px; line-height: 1.5; word-break: break-all; }
+figcaption .name { font-weight: bold; }
+";
+
+/// 写出图片总览：每个有图片保存的输出目录中的 `index.html`，以及位于 `path` 的整次运行总览
+///
+/// 只列出已保存或已链接的图片；预览模式（dry-run）下没有写入图片，页面中不会有图片。
+pub fn write_gallery(path: &Path, results: &[ExtractionResult]) -> Result<()> {
+    let mut file_pages = Vec::with_capacity(results.len());
+    for result in results {
+        let page_path = result.output_directory.join(GALLERY_FILE_NAME);
+        let has_images = gallery_entries(result).next().is_some();
+        if has_images && result.output_directory.is_dir() {
+            let html = file_page(result, &result.output_directory);
+            fs::write(&page_path, html).with_context(|| format!("写入图片总览失败: {}", page_path.display()))?;
+            file_pages.push(Some(page_path));
+        } else {
+            file_pages.push(None);
+        }
+    }
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    let html = run_page(results, &file_pages, base);
+    fs::write(path, html).with_context(|| format!("写入图片总览失败: {}", path.display()))?;
+    Ok(())
+}
+
+/// 总览中显示的图片：已保存或已链接的图片
+fn gallery_entries(result: &ExtractionResult) -> impl Iterator<Item = &ManifestEntry> {
+    result
+        .manifest
+        .iter()
+        .filter(|entry| matches!(entry.status, ImageStatus::Saved | ImageStatus::Linked) && entry.output_path.is_some())
+}
+
+/// 单个源文件的页面，图片链接相对于目录 `base`
+fn file_page(result: &ExtractionResult, base: &Path) -> String {
+    let source = result.source_file.display().to_string();
+    let mut html = page_start(&format!("图片总览 - {}", source));
+    let _ = writeln!(html, "<h1>{}</h1>", escape_html(&source));
+    write_file_summary(&mut html, result);
+    write_image_grid(&mut html, result, base);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// 整次运行的总览页面，按源文件分组，图片链接相对于目录 `base`
+fn run_page(results: &[ExtractionResult], file_pages: &[Option<PathBuf>], base: &Path) -> String {
+    let mut html = page_start("图片总览");
+    html.push_str("<h1>图片总览</h1>\n");
+    let total_images: usize = results.iter().map(|result| gallery_entries(result).count()).sum();
+    let _ = writeln!(
+        html,
+        "<p class=\"summary\">源文件 {} 个，图片 {} 张</p>",
+        results.len(),
+        total_images
+    );
+
+    for (result, page) in results.iter().zip(file_pages) {
+        let source = escape_html(&result.source_file.display().to_string());
+        match page {
+            Some(page) => {
+                let _ = writeln!(html, "<h2><a href=\"{}\">{}</a></h2>", relative_url(base, page), source);
+            }
+            None => {
+                let _ = writeln!(html, "<h2>{}</h2>", source);
+            }
+        }
+        write_file_summary(&mut html, result);
+        write_image_grid(&mut html, result, base);
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn page_start(title: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n",
+        escape_html(title),
+        STYLE
+    )
+}
+
+/// 源文件的输出目录和统计信息，处理失败时为错误信息
+fn write_file_summary(html: &mut String, result: &ExtractionResult) {
+    if let Some(error) = &result.error {
+        let _ = writeln!(html, "<p class=\"error\">处理失败: {}</p>", escape_html(error));
+        return;
+    }
+    let _ = write!(
+        html,
+        "<p class=\"summary\">输出目录: {}<br>找到图片 {} 张，保存 {} 张",
+        escape_html(&result.output_directory.display().to_string()),
+        result.total_images,
+        result.saved_images
+    );
+    if result.duplicate_images > 0 {
+        let _ = write!(html, "，重复图片 {} 张", result.duplicate_images);
+    }
+    if result.near_duplicate_images > 0 {
+        let _ = write!(html, "，相似图片 {} 张", result.near_duplicate_images);
+    }
+    html.push_str("</p>\n");
+}
+
+fn write_image_grid(html: &mut String, result: &ExtractionResult, base: &Path) {
+    let mut entries = gallery_entries(result).peekable();
+    if entries.peek().is_none() {
+        if result.error.is_none() {
+            html.push_str("<p class=\"summary\">没有保存图片</p>\n");
+        }
+        return;
+    }
+
+    html.push_str("<div class=\"grid\">\n");
+    for entry in entries {
+        let Some(output_path) = &entry.output_path else {
+            continue;
+        };
+        let name = output_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let image_url = relative_url(base, output_path);
+        let preview_url = entry
+            .thumbnail_path
+            .as_deref()
+            .map_or_else(|| image_url.clone(), |thumbnail| relative_url(base, thumbnail));
+
+        let mut details = Vec::new();
+        if let (Some(width), Some(height)) = (entry.width, entry.height) {
+            match (entry.resized_width, entry.resized_height) {
+                (Some(resized_width), Some(resized_height)) => {
+                    details.push(format!("{}x{} → {}x{}", width, height, resized_width, resized_height))
+                }
+                _ => details.push(format!("{}x{}", width, height)),
+            }
+        }
+        details.push(format_size(entry.size));
+        details.push(match entry.converted_to {
+            Some(format) if format.mime_type() != entry.image_type => {
+                format!("{} → {}", entry.image_type, format.mime_type())
+            }
+            _ => entry.image_type.clone(),
+        });
+        details.push(format!("偏移 {}-{}", entry.position, entry.end_position));
+        if let Some(original) = &entry.duplicate_of {
+            details.push(format!("与 {} 相同", original.display()));
+        }
+        if let Some(similar_to) = &entry.similar_to {
+            details.push(format!("与 {} 相似", similar_to.display()));
+        }
+        if let Some(repair) = entry.repair {
+            details.push(format!("已修复: {}", repair.label()));
+        }
+        let details = details.iter().map(|detail| escape_html(detail)).collect::<Vec<_>>().join("<br>");
+
+        let _ = writeln!(
+            html,
+            "<figure><a href=\"{url}\"><img src=\"{preview}\" alt=\"{name}\" loading=\"lazy\"></a>\
+             <figcaption><span class=\"name\">{name}</span><br>{details}<br>来源: {source}</figcaption></figure>",
+            url = image_url,
+            preview = preview_url,
+            name = escape_html(&name),
+            details = details,
+            source = escape_html(&result.source_file.display().to_string()),
+        );
+    }
+    html.push_str("</div>\n");
+}
+
+/// 字节数的易读形式
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} bytes", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// `path` 相对于目录 `base` 的URL；无法表示为相对路径时（如位于不同盘符）使用 `file://` 绝对地址
+fn relative_url(base: &Path, path: &Path) -> String {
+    let (base, path) = (normalized_absolute(base), normalized_absolute(path));
+    let base_components: Vec<Component> = base.components().collect();
+    let path_components: Vec<Component> = path.components().collect();
+    if base_components.first() != path_components.first() {
+        let segments: Vec<String> = path_components.iter().filter_map(normal_segment).collect();
+        return format!("file:///{}", segments.join("/"));
+    }
+
+    let common = base_components
+        .iter()
+        .zip(&path_components)
+        .take_while(|(base, path)| base == path)
+        .count();
+    let mut segments = vec!["..".to_string(); base_components.len() - common];
+    segments.extend(path_components[common..].iter().filter_map(normal_segment));
+    segments.join("/")
+}
+
+/// 转为绝对路径并按字面消去 `.` 和 `..`（`std::path::absolute` 在类Unix系统上会保留 `..`）
+fn normalized_absolute(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // 根目录的上级仍是根目录
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// 路径中的普通部分（文件或目录名）经百分号编码后的URL片段，盘符和 `..` 原样保留，根目录返回 `None`
+fn normal_segment(component: &Component) -> Option<String> {
+    match component {
+        Component::Normal(name) => Some(percent_encode(&name.to_string_lossy())),
+        Component::Prefix(prefix) => Some(prefix.as_os_str().to_string_lossy().into_owned()),
+        Component::ParentDir => Some("..".to_string()),
+        _ => None,
+    }
+}
+
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_urls() {
+        let cases = [
+            ("/out/gallery", "/out/gallery/page/image_001.png", "page/image_001.png"),
+            ("/out/gallery", "/out/page/a.png", "../page/a.png"),
+            ("/out/a/b", "/x/y.png", "../../../x/y.png"),
+            ("/out/gallery", "/out/gallery/../img/a.png", "../img/a.png"),
+            ("/out/./gallery/sub/..", "/out/img/a.png", "../img/a.png"),
+            ("/", "/../a.png", "a.png"),
+            ("/out", "/out/图 片/a#1?.png", "%E5%9B%BE%20%E7%89%87/a%231%3F.png"),
+            ("/out", "/out/100%.png", "100%25.png"),
+        ];
+        for (base, path, expected) in cases {
+            assert_eq!(relative_url(Path::new(base), Path::new(path)), expected, "{} -> {}", base, path);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn different_drives_use_file_urls() {
+        assert_eq!(relative_url(Path::new("C:\\out"), Path::new("D:\\img\\a b.png")), "file:///D:/img/a%20b.png");
+    }
+
+    #[test]
+    fn escape_source_paths() {
+        let cases = [
+            ("<script>alert(\"x\")</script>", "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt;"),
+            ("C:\\pages\\Tom & Jerry's.uhtml", "C:\\pages\\Tom &amp; Jerry&#39;s.uhtml"),
+            ("&amp;", "&amp;amp;"),
+            ("/页面/图片.uhtml", "/页面/图片.uhtml"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(escape_html(text), expected, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! UHTML/MHTML图片提取库
+//!
+//! 从UHTML（MHTML）文件中提取内嵌图片：优先按MIME multipart结构解析各部分，
+//! 容器格式异常时回退到按图片签名扫描原始字节，并解码HTML/CSS中的data URI图片。
+//! 命令行工具 `uhtml-pics-parse` 基于本库实现。
+//!
+//! 提取选项汇总在 [`ExtractOptions`] 中，可以用 `with_*` 方法设置，也可以从配置文件加载：
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use uhtml_pics_parse::{DedupMode, ExtractOptions, NameTemplate, UHTMLImageExtractor};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let options = ExtractOptions::new()
+//!     .with_name_template(NameTemplate::parse("{source_stem}_{index}.{ext}")?)
+//!     .with_formats(["jpeg", "png"])
+//!     .with_dedup(DedupMode::Skip);
+//! let extractor = UHTMLImageExtractor::with_options(options);
+//!
+//! let result = extractor.extract_images_from_file(Path::new("page.uhtml"), None)?;
+//! println!("保存了 {} 张图片", result.saved_images);
+//! for entry in &result.manifest {
+//!     println!("{} {:?} {:?}", entry.image_type, entry.status, entry.output_path);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! 只需要图片数据时，[`UHTMLImageExtractor::extract_images_from_bytes`] 和
+//! [`UHTMLImageExtractor::extract_images_from_reader`] 直接返回 [`ImageInfo`]，不读写文件系统；
+//! [`UHTMLImageExtractor::images`] 返回逐张解码的 [`ImageIter`]，可以随时停止迭代。
+
+mod convert;
+mod datauri;
+mod dedup;
+mod extractor;
+mod formats;
+mod gallery;
+mod manifest;
+mod mhtml;
+mod naming;
+mod options;
+mod phash;
+mod progress;
+mod repair;
+mod signatures;
+mod source;
+
+pub use convert::{ConvertFormat, DEFAULT_QUALITY};
+pub use dedup::DedupMode;
+pub use extractor::{is_known_format, ExtractionError, ExtractionResult, ImageInfo, ImageIter, UHTMLImageExtractor};
+pub use formats::Integrity;
+pub use gallery::{write_gallery, GALLERY_FILE_NAME};
+pub use manifest::{write_manifest, ImageStatus, ManifestEntry, ManifestFormat, SkipReason};
+pub use naming::{NameTemplate, DEFAULT_NAME_TEMPLATE};
+pub use options::{AspectRange, ExtractOptions, OverwritePolicy, SizeMatch, DEFAULT_MIN_SIZE};
+pub use phash::{NearDupAction, NearDupCluster, NearDupMember};
+pub use repair::Repair;
+
+/// 签名扫描的内部接口，仅供性能测试（`benches/scan.rs`）使用，不属于稳定的公开API
+#[doc(hidden)]
+pub mod scan {
+    pub use crate::signatures::{find_signatures, scan_and_parse, ImageSignature, IMAGE_SIGNATURES};
+}
@@ -1,10 +1,12 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
 
-mod extractor;
 mod updater;
 
-use extractor::UHTMLImageExtractor;
+use uhtml_pics_parse::{
+    AspectRange, ConvertFormat, DedupMode, ExtractOptions, ExtractionResult, ImageStatus, ManifestEntry, ManifestFormat,
+    is_known_format, NameTemplate, NearDupAction, OverwritePolicy, SizeMatch, UHTMLImageExtractor,
+};
 use updater::Updater;
 
 #[derive(Parser)]
@@ -19,42 +21,293 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// 提取UHTML文件中的图片
-    Extract {
-        /// UHTML文件路径或包含UHTML文件的目录路径
-        path: PathBuf,
-        
-        /// 输出目录（可选，默认使用与文件同名的目录）
-        #[arg(short, long)]
-        output: Option<PathBuf>,
-        
-        /// 递归搜索子目录中的UHTML文件
-        #[arg(short, long)]
-        recursive: bool,
-        
-        /// 详细输出
-        #[arg(short, long)]
-        verbose: bool,
-        
-        /// 输出全部图片（默认过滤小于100x100像素的图片）
-        #[arg(short, long)]
-        all: bool,
-        
-        /// 最小图片尺寸 (格式: 宽x高，例如: 200x150)
-        #[arg(long, value_name = "SIZE")]
-        min_size: Option<String>,
-    },
+    Extract(ExtractArgs),
+
+    /// 列出UHTML文件中的图片及提取时的处理决定（不创建目录、不写入文件）
+    List(ListArgs),
     
     /// 检查并更新到最新版本
     Update,
 }
 
+/// `extract` 子命令的参数
+#[derive(Args)]
+struct ExtractArgs {
+    /// UHTML文件路径或包含UHTML文件的目录路径
+    path: PathBuf,
+    
+    /// 输出目录（可选，默认使用与文件同名的目录）
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    
+    /// 递归搜索子目录中的UHTML文件
+    #[arg(short, long)]
+    recursive: bool,
+    
+    /// 详细输出
+    #[arg(short, long)]
+    verbose: bool,
+
+    #[command(flatten)]
+    options: OptionArgs,
+
+    /// 只扫描不写入：列出将要保存的图片，不创建输出目录和图片文件
+    #[arg(long)]
+    dry_run: bool,
+
+    /// 写出提取清单，格式由扩展名决定（.json 或 .csv），可重复指定以同时输出多种格式
+    #[arg(long, value_name = "FILE")]
+    manifest: Vec<PathBuf>,
+
+    /// 写出HTML图片总览：每个输出目录中生成 index.html，整次运行的总览写入 FILE
+    #[arg(long, value_name = "FILE")]
+    gallery: Option<PathBuf>,
+}
+
+/// `list` 子命令的参数
+#[derive(Args)]
+struct ListArgs {
+    /// UHTML文件路径或包含UHTML文件的目录路径
+    path: PathBuf,
+
+    /// 输出目录（影响按覆盖策略判断文件是否已存在，默认使用与文件同名的目录）
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// 递归搜索子目录中的UHTML文件
+    #[arg(short, long)]
+    recursive: bool,
+
+    #[command(flatten)]
+    options: OptionArgs,
+}
+
+/// 提取选项
+///
+/// 提取选项可以写在配置文件中，命令行中给出的选项覆盖配置文件的设置。
+#[derive(Args)]
+struct OptionArgs {
+    /// 从配置文件（TOML，扩展名为 .json 时按JSON解析）加载提取选项
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    
+    /// 输出全部图片（默认过滤小于100x100像素的图片，其他过滤条件仍然生效）
+    #[arg(short, long)]
+    all: bool,
+    
+    /// 最小图片尺寸 (格式: 宽x高，例如: 200x150)
+    #[arg(long, value_name = "SIZE")]
+    min_size: Option<String>,
+
+    /// 最大图片尺寸 (格式: 宽x高，例如: 4000x3000)
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<String>,
+
+    /// 尺寸过滤规则: both 宽和高都超出范围才过滤（默认）, either 宽或高任一超出范围即过滤
+    #[arg(long, value_enum)]
+    size_match: Option<SizeMatch>,
+
+    /// 最小图片字节数，支持 K、M 后缀（例如: 20K）
+    #[arg(long, value_name = "BYTES", value_parser = parse_byte_size)]
+    min_bytes: Option<usize>,
+
+    /// 最大图片字节数，支持 K、M 后缀（例如: 5M）
+    #[arg(long, value_name = "BYTES", value_parser = parse_byte_size)]
+    max_bytes: Option<usize>,
+
+    /// 宽高比（宽/高）范围 (格式: 最小-最大，可用小数或 宽:高，例如: 4:3-16:9、1.2-)
+    #[arg(long, value_name = "RANGE")]
+    aspect: Option<AspectRange>,
+
+    /// 完整解码校验每张图片，解码失败的图片不输出，错误记录在清单中
+    #[arg(long)]
+    validate: bool,
+
+    /// 尝试修复截断或损坏的JPEG/PNG（补全结束标记、重新编码可读取的部分），无法修复的图片不输出
+    #[arg(long)]
+    repair: bool,
+
+    /// 把无法解码（且未能修复）的图片连同原因说明写入输出目录的 _quarantine 子目录
+    #[arg(long)]
+    quarantine: bool,
+
+    /// 只提取指定格式的图片，多个格式用逗号分隔（例如: jpeg,png）
+    #[arg(long, value_name = "FORMATS", value_delimiter = ',', value_parser = parse_format)]
+    formats: Vec<String>,
+
+    /// 使用原始文件名命名图片（来自Content-Disposition或Content-Location，无原名时按命名模板命名）
+    #[arg(long)]
+    keep_names: bool,
+
+    /// 输出文件命名模板（默认: image_{index}.{ext}），
+    /// 支持占位符: {source_stem} {index} {width} {height} {hash} {ext} {offset} {type}
+    #[arg(long, value_name = "TEMPLATE")]
+    name_template: Option<String>,
+
+    /// 把其他格式的图片转换为该格式后保存: png, jpeg, webp（WebP为无损编码；GIF动画只保留第一帧）
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    convert_to: Option<ConvertFormat>,
+
+    /// 转换或缩放后编码为JPEG时的质量（1-100，默认 85）
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+
+    /// 宽或高超过该值的图片等比缩小后保存（代替原图）
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_dimension: Option<u32>,
+
+    /// 在输出目录的 _thumbnails 子目录中为每张保存的图片生成缩略图 (格式: 宽x高，例如: 320x240)
+    #[arg(long, value_name = "SIZE")]
+    thumbnail: Option<String>,
+
+    /// 重复图片（内容完全相同，跨文件比较）的处理方式: off 不去重（默认）, skip 跳过, link 创建硬链接
+    #[arg(long, value_enum)]
+    dedup: Option<DedupMode>,
+
+    /// 启用相似图片检测，感知哈希距离不超过该值（0-64）的图片视为相似
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=64))]
+    near_dup_threshold: Option<u32>,
+
+    /// 相似图片的处理方式: group 全部保存并分组报告（默认）, drop 只保留每组第一张
+    #[arg(long, value_enum)]
+    near_dup_action: Option<NearDupAction>,
+
+    /// 批量提取时同时处理的文件数（默认 1，0 表示使用全部CPU核心）
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// 输出文件已存在时的处理方式: overwrite 覆盖（默认）, skip 跳过, rename 改用新文件名
+    #[arg(long, value_enum)]
+    overwrite: Option<OverwritePolicy>,
+}
+
+impl OptionArgs {
+    /// 加载配置文件（如有），再用命令行选项覆盖
+    fn options(&self) -> anyhow::Result<ExtractOptions> {
+        let mut options = match &self.config {
+            Some(config) => ExtractOptions::from_file(config)?,
+            None => ExtractOptions::default(),
+        };
+        if self.all {
+            options.output_all = true;
+        }
+        if let Some(size_str) = &self.min_size {
+            if let Some((width, height)) = parse_size_string(size_str)? {
+                options.min_size = (width, height);
+            }
+        }
+        if let Some(size_str) = &self.max_size {
+            if let Some((width, height)) = parse_size_string(size_str)? {
+                options.max_size = Some((width, height));
+            }
+        }
+        if let Some(size_match) = self.size_match {
+            options.size_match = size_match;
+        }
+        if self.min_bytes.is_some() {
+            options.min_bytes = self.min_bytes;
+        }
+        if self.max_bytes.is_some() {
+            options.max_bytes = self.max_bytes;
+        }
+        if let Some(aspect) = &self.aspect {
+            options.aspect = Some(aspect.clone());
+        }
+        if self.validate {
+            options.validate = true;
+        }
+        if self.repair {
+            options.repair = true;
+        }
+        if self.quarantine {
+            options.quarantine = true;
+        }
+        if !self.formats.is_empty() {
+            options.formats = self.formats.clone();
+        }
+        if self.keep_names {
+            options.keep_original_names = true;
+        }
+        if let Some(template) = &self.name_template {
+            options.name_template = NameTemplate::parse(template)?;
+        }
+        if self.convert_to.is_some() {
+            options.convert_to = self.convert_to;
+        }
+        if let Some(quality) = self.quality {
+            options.quality = quality;
+        }
+        if self.max_dimension.is_some() {
+            options.max_dimension = self.max_dimension;
+        }
+        if let Some(size_str) = &self.thumbnail {
+            if let Some((width, height)) = parse_size_string(size_str)? {
+                options.thumbnail = Some((width, height));
+            }
+        }
+        if let Some(dedup) = self.dedup {
+            options.dedup = dedup;
+        }
+        if let Some(threshold) = self.near_dup_threshold {
+            options.near_dup_threshold = Some(threshold);
+        }
+        if let Some(action) = self.near_dup_action {
+            options.near_dup_action = action;
+        }
+        if let Some(jobs) = self.jobs {
+            options.jobs = jobs;
+        }
+        if let Some(overwrite) = self.overwrite {
+            options.overwrite = overwrite;
+        }
+        Ok(options)
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Extract { path, output, recursive, verbose, all, min_size } => {
-            run_extraction(path, output.as_ref(), *recursive, *verbose, *all, min_size.as_deref())?;
+        Commands::Extract(args) => {
+            // 提前检查清单格式，避免提取完成后才报错
+            if let Some(path) = args.manifest.iter().find(|path| ManifestFormat::from_path(path).is_none()) {
+                anyhow::bail!("不支持的清单格式: {}（请使用 .json 或 .csv）", path.display());
+            }
+
+            let mut options = args.options.options()?;
+            if args.dry_run {
+                options.dry_run = true;
+            }
+            let extractor = UHTMLImageExtractor::with_options(options);
+            let results = run_extraction(&extractor, &args.path, args.output.as_ref(), args.recursive, args.verbose)?;
+            if extractor.options().near_dup_threshold.is_some() {
+                print_near_duplicate_report(&extractor);
+            }
+            for manifest_path in &args.manifest {
+                uhtml_pics_parse::write_manifest(manifest_path, &results)?;
+                println!("清单已写入: {}", manifest_path.display());
+            }
+            if let Some(gallery_path) = &args.gallery {
+                if extractor.options().dry_run {
+                    println!("预览模式：不生成图片总览");
+                } else {
+                    uhtml_pics_parse::write_gallery(gallery_path, &results)?;
+                    println!("图片总览已写入: {}", gallery_path.display());
+                }
+            }
+        }
+        Commands::List(args) => {
+            let options = args.options.options()?.with_dry_run(true).with_quiet(true);
+            let extractor = UHTMLImageExtractor::with_options(options);
+            let results = if args.path.is_file() {
+                vec![extractor.extract_images_from_file(&args.path, args.output.as_ref())?]
+            } else if args.path.is_dir() {
+                extractor.extract_images_from_directory(&args.path, args.recursive)?
+            } else {
+                anyhow::bail!("错误: 路径不存在或无效 {}", args.path.display());
+            };
+            print_image_list(&results);
         }
         Commands::Update => {
             let updater = Updater::new()?;
@@ -84,54 +337,216 @@ fn parse_size_string(size_str: &str) -> anyhow::Result<Option<(u32, u32)>> {
     Ok(Some((width, height)))
 }
 
+/// 解析 `--formats` 中的一个格式名，拒绝无法识别的格式
+fn parse_format(format: &str) -> Result<String, String> {
+    if is_known_format(format) {
+        Ok(format.to_string())
+    } else {
+        Err(format!("未知的图片格式: {}（可用格式如 jpeg、png、gif、webp、bmp、tiff、ico、avif、heic、svg，也接受 jpg 等扩展名写法）", format))
+    }
+}
+
+/// 解析字节数，支持 K、M、G 后缀（按1024换算，不区分大小写，可带 B）
+fn parse_byte_size(size_str: &str) -> Result<usize, String> {
+    let text = size_str.trim().to_ascii_uppercase();
+    let text = text.strip_suffix('B').unwrap_or(&text);
+    let (number, multiplier) = match text.char_indices().last() {
+        Some((i, 'K')) => (&text[..i], 1 << 10),
+        Some((i, 'M')) => (&text[..i], 1 << 20),
+        Some((i, 'G')) => (&text[..i], 1 << 30),
+        _ => (text, 1),
+    };
+    number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("字节数格式错误: {}（例如: 20000、20K、5M）", size_str))
+}
+
+/// 以表格形式列出每个文件中的图片及处理决定
+fn print_image_list(results: &[ExtractionResult]) {
+    const HEADERS: [&str; 6] = ["序号", "偏移", "类型", "尺寸", "大小", "决定"];
+
+    let mut total_images = 0;
+    let mut saved_images = 0;
+    let mut linked_images = 0;
+    for result in results {
+        println!("\n{}", result.source_file.display());
+        if let Some(error) = &result.error {
+            println!("  ✗ 失败: {}", error);
+            continue;
+        }
+        if result.manifest.is_empty() {
+            println!("  未找到图片");
+            continue;
+        }
+
+        let rows: Vec<[String; 6]> = result.manifest.iter().map(list_row).collect();
+        let mut widths = HEADERS.map(display_width);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(display_width(cell));
+            }
+        }
+        print_list_row(&HEADERS.map(String::from), &widths);
+        for row in &rows {
+            print_list_row(row, &widths);
+        }
+
+        total_images += result.manifest.len();
+        for entry in result.manifest.iter().filter(|entry| entry.status == ImageStatus::Planned) {
+            if entry.duplicate_of.is_some() {
+                linked_images += 1;
+            } else {
+                saved_images += 1;
+            }
+        }
+    }
+    print!("\n共找到 {} 张图片，将保存 {} 张", total_images, saved_images);
+    if linked_images > 0 {
+        print!("，链接重复图片 {} 张", linked_images);
+    }
+    println!();
+}
+
+/// 表格中一张图片的各列
+fn list_row(entry: &ManifestEntry) -> [String; 6] {
+    let file_name = |path: &Option<PathBuf>| {
+        path.as_deref()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let mut decision = match (entry.status, entry.skip_reason) {
+        (ImageStatus::Failed, _) => format!("失败: {}", entry.error.as_deref().unwrap_or_default()),
+        (ImageStatus::Quarantined, _) => format!("隔离 {}", file_name(&entry.output_path)),
+        (_, Some(reason)) => format!("跳过（{}）", reason.label()),
+        _ => match &entry.duplicate_of {
+            Some(original) => format!("链接 {} -> {}", file_name(&entry.output_path), original.display()),
+            None => format!("保存 {}", file_name(&entry.output_path)),
+        },
+    };
+    if let Some(repair) = entry.repair {
+        decision.push_str(&format!("（已修复: {}）", repair.label()));
+    }
+    let dimensions = match (entry.width, entry.height) {
+        (Some(width), Some(height)) => format!("{}x{}", width, height),
+        _ => "-".to_string(),
+    };
+    [
+        entry.index.map_or_else(|| "-".to_string(), |index| index.to_string()),
+        entry.position.to_string(),
+        entry.image_type.clone(),
+        dimensions,
+        entry.size.to_string(),
+        decision,
+    ]
+}
+
+fn print_list_row(cells: &[String; 6], widths: &[usize; 6]) {
+    let mut line = String::from(" ");
+    for (cell, width) in cells.iter().zip(widths) {
+        line.push(' ');
+        line.push_str(cell);
+        line.extend(std::iter::repeat_n(' ', width - display_width(cell) + 1));
+    }
+    println!("{}", line.trim_end());
+}
+
+/// 终端中的显示宽度，中日韩字符按两列计算
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c >= '\u{1100}' { 2 } else { 1 }).sum()
+}
+
+/// 输出相似图片分组报告
+fn print_near_duplicate_report(extractor: &UHTMLImageExtractor) {
+    let clusters = extractor.near_duplicate_clusters();
+    println!("\n=== 相似图片报告 ===");
+    if clusters.is_empty() {
+        println!("未发现相似图片");
+        return;
+    }
+
+    for (i, cluster) in clusters.iter().enumerate() {
+        println!("相似组 {} ({} 张):", i + 1, cluster.members.len());
+        for member in &cluster.members {
+            let mut notes = Vec::new();
+            if member.distance > 0 {
+                notes.push(format!("距离 {}", member.distance));
+            }
+            if !member.saved {
+                notes.push("未保存".to_string());
+            }
+            if notes.is_empty() {
+                println!("  {}", member.path.display());
+            } else {
+                println!("  {} ({})", member.path.display(), notes.join(", "));
+            }
+        }
+    }
+}
+
 fn run_extraction(
-    path: &PathBuf,
+    extractor: &UHTMLImageExtractor,
+    path: &Path,
     output: Option<&PathBuf>,
     recursive: bool,
     verbose: bool,
-    output_all: bool,
-    min_size: Option<&str>,
-) -> anyhow::Result<()> {
-    let extractor = UHTMLImageExtractor::new();
-    
-    // 解析最小尺寸参数
-    let parsed_min_size = if let Some(size_str) = min_size {
-        parse_size_string(size_str)?
-    } else {
-        None
-    };
+) -> anyhow::Result<Vec<ExtractionResult>> {
+    // 预览模式下图片并未写入，统计信息改用"将保存"
+    let dry_run = extractor.options().dry_run;
+    let saved_label = if dry_run { "将保存" } else { "成功保存" };
+    if dry_run {
+        println!("预览模式：不会创建输出目录或写入图片");
+    }
 
-    if path.is_file() {
+    let results = if path.is_file() {
         // 处理单个文件
-        if !path.extension().map_or(false, |ext| ext == "uhtml") {
+        if path.extension().is_none_or(|ext| ext != "uhtml") {
             anyhow::bail!("错误: 不支持的文件类型 {:?}", path.extension());
         }
 
         println!("提取单个文件: {}", path.display());
-        let result = extractor.extract_images_from_file(path, output, output_all, parsed_min_size)?;
+        let result = extractor.extract_images_from_file(path, output)?;
 
         println!("\n=== 提取完成 ===");
         println!("源文件: {}", result.source_file.display());
         println!("输出目录: {}", result.output_directory.display());
         println!("找到图片: {} 张", result.total_images);
-        println!("成功保存: {} 张", result.saved_images);
+        println!("{}: {} 张", saved_label, result.saved_images);
+        if result.duplicate_images > 0 {
+            println!("重复图片: {} 张", result.duplicate_images);
+        }
+        if result.near_duplicate_images > 0 {
+            println!("相似图片: {} 张", result.near_duplicate_images);
+        }
 
+        vec![result]
     } else if path.is_dir() {
         // 处理目录
         println!("批量提取目录: {}", path.display());
         println!("递归搜索: {}", if recursive { "是" } else { "否" });
 
-        let results = extractor.extract_images_from_directory(path, recursive, output_all, parsed_min_size)?;
+        let results = extractor.extract_images_from_directory(path, recursive)?;
 
         // 统计结果
         let total_files = results.len();
         let successful_files = results.iter().filter(|r| r.error.is_none()).count();
         let total_images: usize = results.iter().map(|r| r.saved_images).sum();
+        let duplicate_images: usize = results.iter().map(|r| r.duplicate_images).sum();
+        let near_duplicate_images: usize = results.iter().map(|r| r.near_duplicate_images).sum();
 
         println!("\n=== 批量提取完成 ===");
         println!("处理文件: {} 个", total_files);
         println!("成功文件: {} 个", successful_files);
-        println!("提取图片总数: {} 张", total_images);
+        println!("{}图片总数: {} 张", if dry_run { "将提取" } else { "提取" }, total_images);
+        if duplicate_images > 0 {
+            println!("重复图片: {} 张", duplicate_images);
+        }
+        if near_duplicate_images > 0 {
+            println!("相似图片: {} 张", near_duplicate_images);
+        }
 
         if verbose {
             println!("\n=== 详细结果 ===");
@@ -144,9 +559,10 @@ fn run_extraction(
             }
         }
 
+        results
     } else {
         anyhow::bail!("错误: 路径不存在或无效 {}", path.display());
-    }
+    };
 
-    Ok(())
+    Ok(results)
 }
@@ -0,0 +1,347 @@
+//! 提取清单
+//!
+//! 记录每张图片（包括被跳过的图片）的来源、位置、类型、尺寸、哈希、
+//! 输出路径和跳过原因，按JSON或CSV格式写出，便于其他工具处理提取结果。
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::convert::ConvertFormat;
+use crate::extractor::{ExtractionResult, ImageInfo};
+use crate::formats::Integrity;
+use crate::repair::Repair;
+
+/// 图片的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageStatus {
+    /// 已写入输出目录
+    Saved,
+    /// 与之前的图片内容相同，已创建硬链接（或副本）
+    Linked,
+    /// 未输出，原因见 `skip_reason`
+    Skipped,
+    /// 预览模式（dry-run）下将会保存或链接，实际未写入
+    Planned,
+    /// 保存失败，错误见 `error`
+    Failed,
+    /// 无法解码，原始数据已写入 `_quarantine` 子目录（`output_path`），原因见 `skip_reason` 和 `error`
+    Quarantined,
+}
+
+/// 图片未输出的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// 数据不足100字节，不视为有效图片
+    TooFewBytes,
+    /// 字节数小于 `min_bytes`
+    BelowMinBytes,
+    /// 字节数大于 `max_bytes`
+    AboveMaxBytes,
+    /// 格式不在 `formats` 选项中
+    FormatExcluded,
+    /// 无法从文件头解析出尺寸，可能不是有效图片
+    UnknownDimensions,
+    /// 校验模式下完整解码失败，错误见 `error`
+    DecodeFailed,
+    /// 小于最小尺寸（按 `size_match` 规则判断）
+    BelowMinSize,
+    /// 大于最大尺寸（按 `size_match` 规则判断）
+    AboveMaxSize,
+    /// 宽高比不在 `aspect` 范围内
+    AspectExcluded,
+    /// 与之前保存过的图片内容相同
+    Duplicate,
+    /// 与之前的图片相似（`drop` 模式）
+    NearDuplicate,
+    /// 输出文件已存在（覆盖策略为 `skip`）
+    AlreadyExists,
+    /// 写入文件失败
+    SaveFailed,
+}
+
+impl SkipReason {
+    /// 用于输出的中文描述
+    pub fn label(&self) -> &'static str {
+        match self {
+            SkipReason::TooFewBytes => "数据过小",
+            SkipReason::BelowMinBytes => "字节数过小",
+            SkipReason::AboveMaxBytes => "字节数过大",
+            SkipReason::FormatExcluded => "格式未选中",
+            SkipReason::UnknownDimensions => "无法解析尺寸",
+            SkipReason::DecodeFailed => "解码失败",
+            SkipReason::BelowMinSize => "尺寸过小",
+            SkipReason::AboveMaxSize => "尺寸过大",
+            SkipReason::AspectExcluded => "宽高比不符",
+            SkipReason::Duplicate => "重复图片",
+            SkipReason::NearDuplicate => "相似图片",
+            SkipReason::AlreadyExists => "文件已存在",
+            SkipReason::SaveFailed => "保存失败",
+        }
+    }
+}
+
+/// 清单中的一条图片记录
+#[derive(Debug, Clone, Serialize)]
+pub struct ManifestEntry {
+    /// 图片序号（与命名模板的 `{index}` 一致），被尺寸过滤的图片没有序号
+    pub index: Option<usize>,
+    /// 在源文件中的起止字节偏移
+    pub position: usize,
+    pub end_position: usize,
+    /// MIME类型，如 `image/jpeg`
+    pub image_type: String,
+    /// 像素尺寸，未解析尺寸时为空
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 图片数据字节数
+    pub size: usize,
+    /// 图片内容的SHA-256（十六进制小写）
+    pub hash: String,
+    pub integrity: Integrity,
+    /// 修复方式，未修复时为空
+    pub repair: Option<Repair>,
+    pub status: ImageStatus,
+    pub skip_reason: Option<SkipReason>,
+    /// 输出文件路径（已保存、已链接或将要保存时；因文件已存在跳过时为已有文件；隔离时为隔离文件）
+    pub output_path: Option<PathBuf>,
+    /// 保存时转换成的格式，按原始数据保存时为空
+    pub converted_to: Option<ConvertFormat>,
+    /// 按 `max_dimension` 缩小后保存的尺寸，按原尺寸保存时为空
+    pub resized_width: Option<u32>,
+    pub resized_height: Option<u32>,
+    /// 缩略图路径，未生成缩略图时为空
+    pub thumbnail_path: Option<PathBuf>,
+    /// 内容相同的首次保存文件（重复图片）
+    pub duplicate_of: Option<PathBuf>,
+    /// 相似组的代表图片及感知哈希距离
+    pub similar_to: Option<PathBuf>,
+    pub distance: Option<u32>,
+    /// 保存失败或解码失败时的错误信息
+    pub error: Option<String>,
+}
+
+impl ManifestEntry {
+    /// 按图片信息创建记录，输出路径等字段留空
+    pub fn for_image(image: &ImageInfo, status: ImageStatus) -> Self {
+        Self {
+            index: Some(image.index),
+            position: image.position,
+            end_position: image.end_position,
+            image_type: image.image_type.clone(),
+            width: image.width,
+            height: image.height,
+            size: image.size,
+            hash: image.hash.clone(),
+            integrity: image.integrity,
+            repair: image.repair,
+            status,
+            skip_reason: None,
+            output_path: None,
+            converted_to: None,
+            resized_width: None,
+            resized_height: None,
+            thumbnail_path: None,
+            duplicate_of: None,
+            similar_to: None,
+            distance: None,
+            error: None,
+        }
+    }
+}
+
+/// 清单文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Csv,
+}
+
+impl ManifestFormat {
+    /// 按扩展名（`.json` / `.csv`）确定清单格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(ManifestFormat::Json),
+            "csv" => Some(ManifestFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// JSON清单的顶层结构
+#[derive(Serialize)]
+struct JsonManifest<'a> {
+    files: &'a [ExtractionResult],
+}
+
+/// CSV清单的列名，与 `CsvRow` 的字段一一对应
+const CSV_HEADERS: &[&str] = &[
+    "source_file", "index", "position", "end_position", "image_type", "width", "height", "size", "hash",
+    "integrity", "repair", "status", "skip_reason", "output_path", "converted_to", "resized_width",
+    "resized_height", "thumbnail_path", "duplicate_of", "similar_to", "distance", "error",
+];
+
+/// CSV清单的一行：每张图片一行，附带源文件路径；处理失败的文件占一行，只有 `source_file` 和 `error`
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    source_file: &'a Path,
+    index: Option<usize>,
+    position: Option<usize>,
+    end_position: Option<usize>,
+    image_type: Option<&'a str>,
+    width: Option<u32>,
+    height: Option<u32>,
+    size: Option<usize>,
+    hash: Option<&'a str>,
+    integrity: Option<Integrity>,
+    repair: Option<Repair>,
+    status: Option<ImageStatus>,
+    skip_reason: Option<SkipReason>,
+    output_path: Option<&'a Path>,
+    converted_to: Option<ConvertFormat>,
+    resized_width: Option<u32>,
+    resized_height: Option<u32>,
+    thumbnail_path: Option<&'a Path>,
+    duplicate_of: Option<&'a Path>,
+    similar_to: Option<&'a Path>,
+    distance: Option<u32>,
+    error: Option<&'a str>,
+}
+
+impl<'a> CsvRow<'a> {
+    /// 处理失败的文件：图片相关的列留空
+    fn file_error(source_file: &'a Path, error: &'a str) -> Self {
+        Self {
+            source_file,
+            index: None,
+            position: None,
+            end_position: None,
+            image_type: None,
+            width: None,
+            height: None,
+            size: None,
+            hash: None,
+            integrity: None,
+            repair: None,
+            status: None,
+            skip_reason: None,
+            output_path: None,
+            converted_to: None,
+            resized_width: None,
+            resized_height: None,
+            thumbnail_path: None,
+            duplicate_of: None,
+            similar_to: None,
+            distance: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// 按扩展名确定的格式写出本次运行的提取清单
+pub fn write_manifest(path: &Path, results: &[ExtractionResult]) -> Result<()> {
+    let format = ManifestFormat::from_path(path)
+        .with_context(|| format!("不支持的清单格式: {}（请使用 .json 或 .csv）", path.display()))?;
+    let file = File::create(path).with_context(|| format!("创建清单文件失败: {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        ManifestFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &JsonManifest { files: results })
+                .with_context(|| format!("写入清单文件失败: {}", path.display()))?;
+            writeln!(writer)?;
+        }
+        ManifestFormat::Csv => write_csv(&mut writer, results)
+            .with_context(|| format!("写入清单文件失败: {}", path.display()))?,
+    }
+
+    writer.flush().with_context(|| format!("写入清单文件失败: {}", path.display()))?;
+    Ok(())
+}
+
+/// 写出CSV清单；没有任何记录时也写出列名
+fn write_csv(writer: impl Write, results: &[ExtractionResult]) -> csv::Result<()> {
+    let mut csv_writer = csv::WriterBuilder::new().has_headers(false).from_writer(writer);
+    csv_writer.write_record(CSV_HEADERS)?;
+    for result in results {
+        if let Some(error) = &result.error {
+            csv_writer.serialize(CsvRow::file_error(&result.source_file, error))?;
+        }
+        for entry in &result.manifest {
+            csv_writer.serialize(CsvRow {
+                source_file: &result.source_file,
+                index: entry.index,
+                position: Some(entry.position),
+                end_position: Some(entry.end_position),
+                image_type: Some(&entry.image_type),
+                width: entry.width,
+                height: entry.height,
+                size: Some(entry.size),
+                hash: Some(&entry.hash),
+                integrity: Some(entry.integrity),
+                repair: entry.repair,
+                status: Some(entry.status),
+                skip_reason: entry.skip_reason,
+                output_path: entry.output_path.as_deref(),
+                converted_to: entry.converted_to,
+                resized_width: entry.resized_width,
+                resized_height: entry.resized_height,
+                thumbnail_path: entry.thumbnail_path.as_deref(),
+                duplicate_of: entry.duplicate_of.as_deref(),
+                similar_to: entry.similar_to.as_deref(),
+                distance: entry.distance,
+                error: entry.error.as_deref(),
+            })?;
+        }
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn csv_lines(results: &[ExtractionResult]) -> Vec<String> {
+        let mut output = Vec::new();
+        write_csv(&mut output, results).unwrap();
+        String::from_utf8(output).unwrap().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn headers_match_row_fields() {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(CsvRow::file_error(Path::new("a.uhtml"), "x")).unwrap();
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(output.lines().next(), Some(CSV_HEADERS.join(",").as_str()));
+    }
+
+    #[test]
+    fn empty_csv_has_header() {
+        assert_eq!(csv_lines(&[]), [CSV_HEADERS.join(",")]);
+    }
+
+    #[test]
+    fn failed_file_has_error_row() {
+        let failed = ExtractionResult {
+            source_file: PathBuf::from("broken.uhtml"),
+            output_directory: PathBuf::new(),
+            total_images: 0,
+            saved_images: 0,
+            duplicate_images: 0,
+            near_duplicate_images: 0,
+            error: Some("读取文件失败".to_string()),
+            manifest: Vec::new(),
+        };
+        let lines = csv_lines(&[failed]);
+        assert_eq!(lines.len(), 2);
+        let empty_columns = ",".repeat(CSV_HEADERS.len() - 2);
+        assert_eq!(lines[1], format!("broken.uhtml{},读取文件失败", empty_columns));
+    }
+}
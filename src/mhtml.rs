@@ -0,0 +1,406 @@
+//! MHTML/UHTML容器解析
+//!
+//! 按MIME multipart结构读取分隔边界和各部分的头部，
+//! 并解码 base64 / quoted-printable 编码的内容。
+
+use std::borrow::Cow;
+
+use base64::alphabet;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use memchr::memmem;
+
+use crate::extractor::ExtractionError;
+
+/// 嵌套multipart的最大层数
+const MAX_NESTING: usize = 8;
+
+/// 对填充不敏感的base64解码器（部分保存工具会省略末尾的 `=`）
+const LENIENT_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// MIME容器中的一个叶子部分
+#[derive(Debug)]
+pub struct MimePart<'a> {
+    /// 小写、不含参数的内容类型，如 `image/jpeg`
+    pub content_type: String,
+    /// 小写的传输编码，如 `base64`
+    pub transfer_encoding: String,
+    pub content_location: Option<String>,
+    pub content_id: Option<String>,
+    /// `Content-Disposition` 中的文件名（或 `Content-Type` 的 `name` 参数）
+    pub filename: Option<String>,
+    /// 编码内容在容器中的起始位置
+    pub offset: usize,
+    /// 未解码的内容
+    pub raw_body: &'a [u8],
+}
+
+impl<'a> MimePart<'a> {
+    /// 内容是否未经编码（解码后的偏移与容器中的偏移一致）
+    pub fn is_identity_encoded(&self) -> bool {
+        matches!(self.transfer_encoding.as_str(), "" | "7bit" | "8bit" | "binary")
+    }
+
+    /// 按 `Content-Transfer-Encoding` 解码内容
+    ///
+    /// 未编码的内容直接借用容器中的字节，不做复制。
+    pub fn decode_body(&self) -> Result<Cow<'a, [u8]>, ExtractionError> {
+        match self.transfer_encoding.as_str() {
+            "base64" => Ok(Cow::Owned(decode_base64(self.raw_body)?)),
+            "quoted-printable" => Ok(Cow::Owned(decode_quoted_printable(self.raw_body))),
+            // 7bit / 8bit / binary 以及未声明编码时按原样返回
+            _ => Ok(Cow::Borrowed(self.raw_body)),
+        }
+    }
+}
+
+/// 解码base64内容，忽略其中的换行和空白
+pub fn decode_base64(encoded: &[u8]) -> Result<Vec<u8>, ExtractionError> {
+    let compact: Vec<u8> = encoded
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    Ok(LENIENT_BASE64.decode(compact)?)
+}
+
+/// 解析MIME multipart容器，返回所有叶子部分
+///
+/// 数据不是multipart容器（缺少头部、类型不是multipart或没有边界参数）
+/// 或其中找不到任何部分时返回 `None`。
+pub fn parse_multipart(data: &[u8]) -> Option<Vec<MimePart<'_>>> {
+    let (headers, body_start) = parse_headers(data, 0)?;
+    let content_type = header_value(&headers, "content-type")?;
+    if !media_type(content_type).starts_with("multipart/") {
+        return None;
+    }
+    let boundary = header_param(content_type, "boundary")?;
+
+    let mut parts = Vec::new();
+    parse_multipart_body(data, body_start, data.len(), &boundary, 0, &mut parts);
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts)
+    }
+}
+
+/// 按边界拆分multipart内容，嵌套的multipart部分递归展开
+fn parse_multipart_body<'a>(
+    data: &'a [u8],
+    start: usize,
+    end: usize,
+    boundary: &str,
+    depth: usize,
+    parts: &mut Vec<MimePart<'a>>,
+) {
+    if depth > MAX_NESTING {
+        return;
+    }
+
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let Some(mut pos) = find_delimiter(data, &delimiter, start, end) else {
+        return;
+    };
+
+    loop {
+        let after = pos + delimiter.len();
+        // 结束边界
+        if data.get(after..after + 2) == Some(b"--") {
+            return;
+        }
+        // 跳过边界行剩余部分
+        let part_start = match data[after..end].iter().position(|&b| b == b'\n') {
+            Some(offset) => after + offset + 1,
+            None => return,
+        };
+
+        // 下一个边界之前的换行属于边界本身；找不到下一个边界时视为内容被截断
+        let next = find_delimiter(data, &delimiter, part_start, end);
+        let mut part_end = next.unwrap_or(end);
+        if next.is_some() {
+            if part_end > part_start && data[part_end - 1] == b'\n' {
+                part_end -= 1;
+            }
+            if part_end > part_start && data[part_end - 1] == b'\r' {
+                part_end -= 1;
+            }
+        }
+
+        parse_part(data, part_start, part_end, depth, parts);
+
+        match next {
+            Some(next_pos) => pos = next_pos,
+            None => return,
+        }
+    }
+}
+
+/// 解析单个部分的头部和内容
+fn parse_part<'a>(data: &'a [u8], start: usize, end: usize, depth: usize, parts: &mut Vec<MimePart<'a>>) {
+    let Some((headers, body_start)) = parse_headers(&data[..end], start) else {
+        return;
+    };
+
+    let content_type = header_value(&headers, "content-type").unwrap_or("text/plain");
+    let media = media_type(content_type);
+    if media.starts_with("multipart/") {
+        if let Some(boundary) = header_param(content_type, "boundary") {
+            parse_multipart_body(data, body_start, end, &boundary, depth + 1, parts);
+        }
+        return;
+    }
+
+    parts.push(MimePart {
+        content_type: media,
+        transfer_encoding: header_value(&headers, "content-transfer-encoding")
+            .map(|value| value.trim().to_ascii_lowercase())
+            .unwrap_or_default(),
+        content_location: header_value(&headers, "content-location").map(|value| value.trim().to_string()),
+        content_id: header_value(&headers, "content-id")
+            .map(|value| value.trim().trim_start_matches('<').trim_end_matches('>').to_string()),
+        filename: header_value(&headers, "content-disposition")
+            .and_then(disposition_filename)
+            .or_else(|| header_param(content_type, "name")),
+        offset: body_start,
+        raw_body: &data[body_start..end],
+    });
+}
+
+/// 取 `Content-Disposition` 中的文件名，优先使用RFC 2231编码的 `filename*`
+fn disposition_filename(value: &str) -> Option<String> {
+    if let Some(extended) = header_param(value, "filename*") {
+        // 字符集'语言'百分号编码的值
+        let encoded = extended.splitn(3, '\'').nth(2).unwrap_or(&extended);
+        return Some(String::from_utf8_lossy(&percent_decode(encoded.as_bytes())).into_owned());
+    }
+    header_param(value, "filename")
+}
+
+/// 查找位于行首的边界
+fn find_delimiter(data: &[u8], delimiter: &[u8], start: usize, end: usize) -> Option<usize> {
+    let mut pos = start;
+    while pos < end {
+        let offset = memmem::find(&data[pos..end], delimiter)?;
+        let found = pos + offset;
+        if found == start || data[found - 1] == b'\n' {
+            return Some(found);
+        }
+        pos = found + 1;
+    }
+    None
+}
+
+/// 头部单行的最大长度（RFC 5322 限制为 998 字节，这里留出余量）
+const MAX_HEADER_LINE: usize = 8 * 1024;
+
+/// 解析头部块，返回 (名称小写, 值) 列表和内容起始位置
+///
+/// 续行（以空白开头的行）会合并到上一个头部；某行超过 `MAX_HEADER_LINE` 时视为不是头部。
+fn parse_headers(data: &[u8], start: usize) -> Option<(Vec<(String, String)>, usize)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut pos = start;
+    loop {
+        let limit = data.len().min(pos + MAX_HEADER_LINE + 1);
+        let line_end = data[pos..limit].iter().position(|&b| b == b'\n').map(|offset| pos + offset);
+        if line_end.is_none() && limit < data.len() {
+            return None;
+        }
+        let raw_line = &data[pos..line_end.unwrap_or(data.len())];
+        let line = String::from_utf8_lossy(raw_line.strip_suffix(b"\r").unwrap_or(raw_line));
+        let next = line_end.map_or(data.len(), |line_end| line_end + 1);
+
+        if line.is_empty() {
+            return Some((headers, next));
+        }
+
+        if line.starts_with([' ', '\t']) {
+            // 续行
+            let (_, value) = headers.last_mut()?;
+            value.push(' ');
+            value.push_str(line.trim());
+        } else {
+            let (name, value) = line.split_once(':')?;
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return None;
+            }
+            headers.push((name.to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        // 头部没有以空行结束
+        line_end?;
+        pos = next;
+    }
+}
+
+fn header_value<'h>(headers: &'h [(String, String)], name: &str) -> Option<&'h str> {
+    headers
+        .iter()
+        .find(|(header_name, _)| header_name == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// 取头部值中 `;` 之前的媒体类型，转为小写
+fn media_type(value: &str) -> String {
+    value.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+/// 取头部值中的参数，如 `boundary="abc"`
+fn header_param(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, param_value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(name) {
+            Some(param_value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// 解码quoted-printable内容，无效的转义序列按原样保留
+fn decode_quoted_printable(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    while pos < data.len() {
+        if data[pos] != b'=' {
+            decoded.push(data[pos]);
+            pos += 1;
+            continue;
+        }
+
+        match &data[pos + 1..] {
+            // 软换行
+            [b'\r', b'\n', ..] => pos += 3,
+            [b'\n', ..] => pos += 2,
+            [high, low, ..] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                decoded.push(hex_value(*high) << 4 | hex_value(*low));
+                pos += 3;
+            }
+            _ => {
+                decoded.push(b'=');
+                pos += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn hex_value(digit: u8) -> u8 {
+    (digit as char).to_digit(16).unwrap_or(0) as u8
+}
+
+/// 百分号解码（URL路径和RFC 2231参数值），无效的转义序列按原样保留
+pub fn percent_decode(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    while pos < data.len() {
+        match &data[pos..] {
+            [b'%', high, low, ..] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+                decoded.push(hex_value(*high) << 4 | hex_value(*low));
+                pos += 3;
+            }
+            [byte, ..] => {
+                decoded.push(*byte);
+                pos += 1;
+            }
+            [] => break,
+        }
+    }
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MHTML: &str = "MIME-Version: 1.0\r\n\
+Content-Type: multipart/related;\r\n\
+\ttype=\"text/html\"; boundary=\"outer\"\r\n\
+\r\n\
+--outer\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+Content-Location: https://example.com/\r\n\
+\r\n\
+<img src=3D\"a.png\">caf=C3=A9 soft=\r\n\
+break\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=inner\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: image/png\r\n\
+Content-Transfer-Encoding: base64\r\n\
+Content-ID: <img1@example>\r\n\
+\r\n\
+aGVsbG8g\r\n\
+d29ybGQ\r\n\
+--inner\r\n\
+Content-Type: image/gif\r\n\
+\r\n\
+GIF89a\r\n\
+--inner--\r\n\
+--outer--\r\n";
+
+    #[test]
+    fn quoted_printable_part() {
+        let parts = parse_multipart(MHTML.as_bytes()).unwrap();
+        let html = &parts[0];
+        assert_eq!(html.content_type, "text/html");
+        assert_eq!(html.content_location.as_deref(), Some("https://example.com/"));
+        assert!(!html.is_identity_encoded());
+        assert_eq!(
+            html.decode_body().unwrap().as_ref(),
+            "<img src=\"a.png\">café softbreak".as_bytes()
+        );
+    }
+
+    #[test]
+    fn base64_part_with_line_breaks_and_missing_padding() {
+        let parts = parse_multipart(MHTML.as_bytes()).unwrap();
+        let image = &parts[1];
+        assert_eq!(image.content_type, "image/png");
+        assert_eq!(image.transfer_encoding, "base64");
+        assert_eq!(image.content_id.as_deref(), Some("img1@example"));
+        assert_eq!(image.decode_body().unwrap().as_ref(), b"hello world");
+    }
+
+    #[test]
+    fn nested_multipart_parts_are_flattened() {
+        let data = MHTML.as_bytes();
+        let parts = parse_multipart(data).unwrap();
+        let types: Vec<_> = parts.iter().map(|part| part.content_type.as_str()).collect();
+        assert_eq!(types, ["text/html", "image/png", "image/gif"]);
+
+        // 未编码的部分直接借用容器中的字节，偏移指向内容的起始位置
+        let gif = &parts[2];
+        assert!(gif.is_identity_encoded());
+        assert_eq!(gif.raw_body, b"GIF89a");
+        assert_eq!(&data[gif.offset..gif.offset + 6], b"GIF89a");
+        assert!(matches!(gif.decode_body().unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn non_multipart_data_is_rejected() {
+        assert!(parse_multipart(b"<html><body></body></html>").is_none());
+        assert!(parse_multipart(b"Content-Type: text/html\r\n\r\n<html></html>").is_none());
+        assert!(parse_multipart(b"Content-Type: multipart/related\r\n\r\n--x\r\n").is_none());
+    }
+
+    #[test]
+    fn overlong_header_line_is_rejected() {
+        let mut data = b"Content-Type: text/html\r\nX-Padding: ".to_vec();
+        data.extend_from_slice(&[b'a'; MAX_HEADER_LINE]);
+        data.extend_from_slice(b"\r\n\r\n<html></html>");
+        assert!(parse_headers(&data, 0).is_none());
+
+        let line = format!("X-Padding: {}", "a".repeat(MAX_HEADER_LINE - 20));
+        let data = format!("Content-Type: text/html\r\n{}\r\n\r\nbody", line);
+        let (headers, start) = parse_headers(data.as_bytes(), 0).unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(&data[start..], "body");
+    }
+}
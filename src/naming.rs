@@ -0,0 +1,440 @@
+//! 输出文件命名
+//!
+//! 按命名模板或原始资源名（`Content-Disposition` 文件名或 `Content-Location` URL）
+//! 生成安全的文件名，并在同一输出目录内处理重名。
+
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::extractor::{ExtractionError, ImageInfo};
+use crate::mhtml;
+
+/// 默认命名模板，与早期版本的 `image_001.jpg` 保持一致
+pub const DEFAULT_NAME_TEMPLATE: &str = "image_{index}.{ext}";
+
+/// `{hash}` 使用的SHA-256十六进制前缀长度
+const HASH_PREFIX_LEN: usize = 16;
+
+/// 尺寸未知的图片（如SVG）在 `{width}` / `{height}` 处使用的文字
+const UNKNOWN_DIMENSION: &str = "unknown";
+
+/// 文件名主体的最大字符数
+const MAX_STEM_CHARS: usize = 100;
+
+/// Windows保留的设备名
+const RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8", "com9",
+    "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+/// 从 `Content-Location` 取URL路径的最后一段作为文件名
+///
+/// 去掉查询参数和片段并做百分号解码；`cid:`、`data:` 等没有路径的地址返回 `None`。
+pub fn name_from_location(location: &str) -> Option<String> {
+    let location = location.trim();
+    let lower = location.to_ascii_lowercase();
+    if lower.starts_with("cid:") || lower.starts_with("data:") || lower.starts_with("about:") {
+        return None;
+    }
+
+    let path = location.split(['?', '#']).next()?;
+    // 去掉 scheme://host 部分，只保留路径
+    let path = match path.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map_or("", |(_, path)| path),
+        None => path,
+    };
+    let segment = path.rsplit(['/', '\\']).next()?;
+    if segment.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&mhtml::percent_decode(segment.as_bytes())).into_owned())
+}
+
+/// 清理文件名中各平台不允许的字符
+///
+/// 替换路径分隔符、控制字符和Windows保留字符，去掉首尾的点和空格，
+/// 限制长度，并避开Windows设备名。清理后为空时返回 `None`。
+pub fn sanitize_file_name(name: &str) -> Option<String> {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if cleaned.is_empty() {
+        return None;
+    }
+
+    // 保留扩展名，只截断主体
+    let (stem, extension) = match cleaned.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && extension.chars().count() <= 10 => (stem, Some(extension)),
+        _ => (cleaned, None),
+    };
+    let mut stem: String = stem.chars().take(MAX_STEM_CHARS).collect();
+    // Windows按第一个点之前的部分判断设备名，`nul.tar.gz` 同样指向设备
+    let device = stem.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.contains(&device.to_ascii_lowercase().as_str()) {
+        stem.insert(0, '_');
+    }
+
+    Some(match extension {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem,
+    })
+}
+
+/// 确保文件名带有与图片类型一致的扩展名
+///
+/// `equivalent` 是该类型可接受的扩展名（不含点，小写）；扩展名不一致时追加 `extension`。
+pub fn with_extension(name: &str, extension: &str, equivalent: &[&str]) -> String {
+    let matches = name
+        .rsplit_once('.')
+        .is_some_and(|(_, current)| equivalent.contains(&current.to_ascii_lowercase().as_str()));
+    if matches {
+        name.to_string()
+    } else {
+        format!("{}{}", name, extension)
+    }
+}
+
+/// 去掉属于 `equivalent` 的扩展名，用于转换格式后替换原来的扩展名
+pub fn strip_extension<'n>(name: &'n str, equivalent: &[&str]) -> &'n str {
+    match name.rsplit_once('.') {
+        Some((stem, current)) if !stem.is_empty() && equivalent.contains(&current.to_ascii_lowercase().as_str()) => stem,
+        _ => name,
+    }
+}
+
+/// 记录同一输出目录中已使用的文件名，重名时追加序号
+#[derive(Debug, Default)]
+pub struct UniqueNames {
+    used: HashSet<String>,
+}
+
+impl UniqueNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 占用一个文件名；已被占用时改为 `name_1.ext`、`name_2.ext` ……
+    ///
+    /// 比较时忽略大小写，避免在不区分大小写的文件系统上互相覆盖。
+    pub fn claim(&mut self, name: &str) -> String {
+        if self.used.insert(name.to_lowercase()) {
+            return name.to_string();
+        }
+
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name, String::new()),
+        };
+        let mut counter = 1;
+        loop {
+            let candidate = format!("{}_{}{}", stem, counter, extension);
+            if self.used.insert(candidate.to_lowercase()) {
+                return candidate;
+            }
+            counter += 1;
+        }
+    }
+}
+
+/// 命名模板中的占位符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// 源UHTML文件名（不含扩展名）
+    SourceStem,
+    /// 图片序号（三位补零）
+    Index,
+    Width,
+    Height,
+    /// 图片内容SHA-256的十六进制前缀
+    Hash,
+    /// 扩展名（不含点）
+    Ext,
+    /// 图片在源文件中的字节偏移
+    Offset,
+    /// 图片类型，如 `jpeg`
+    Type,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "source_stem" => Some(Placeholder::SourceStem),
+            "index" => Some(Placeholder::Index),
+            "width" => Some(Placeholder::Width),
+            "height" => Some(Placeholder::Height),
+            "hash" => Some(Placeholder::Hash),
+            "ext" => Some(Placeholder::Ext),
+            "offset" => Some(Placeholder::Offset),
+            "type" => Some(Placeholder::Type),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// 输出文件命名模板，如 `{source_stem}_{index}_{width}x{height}.{ext}`
+///
+/// 支持的占位符：`{source_stem}`、`{index}`、`{width}`、`{height}`、`{hash}`、
+/// `{ext}`、`{offset}`、`{type}`。`{{` 和 `}}` 表示字面的花括号。
+/// 生成的文件名没有对应的扩展名时，提取器会自动追加。
+/// 序列化为模板字符串本身。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NameTemplate {
+    /// 原始模板字符串
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl NameTemplate {
+    /// 解析命名模板，未知或未闭合的占位符视为错误
+    pub fn parse(template: &str) -> Result<Self, ExtractionError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(ExtractionError::NameTemplate(format!("占位符未闭合: {{{}", name)));
+                            }
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(&name)
+                        .ok_or_else(|| ExtractionError::NameTemplate(format!("未知占位符: {{{}}}", name)))?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder(placeholder));
+                }
+                '}' => return Err(ExtractionError::NameTemplate("多余的 '}'，字面花括号请写作 '}}'".to_string())),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if segments.is_empty() {
+            return Err(ExtractionError::NameTemplate("模板不能为空".to_string()));
+        }
+        Ok(Self {
+            source: template.to_string(),
+            segments,
+        })
+    }
+
+    /// 按图片信息生成文件名（未做文件名清理）
+    ///
+    /// `extension` 为带点的扩展名，如 `.jpg`。
+    pub fn render(&self, source_stem: &str, index: usize, image: &ImageInfo, extension: &str) -> String {
+        let mut name = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => name.push_str(text),
+                Segment::Placeholder(placeholder) => match placeholder {
+                    Placeholder::SourceStem => name.push_str(source_stem),
+                    Placeholder::Index => name.push_str(&format!("{:03}", index)),
+                    Placeholder::Width => name.push_str(&dimension_text(image.width)),
+                    Placeholder::Height => name.push_str(&dimension_text(image.height)),
+                    Placeholder::Hash => name.push_str(&image.hash[..HASH_PREFIX_LEN]),
+                    Placeholder::Ext => name.push_str(extension.trim_start_matches('.')),
+                    Placeholder::Offset => name.push_str(&image.position.to_string()),
+                    Placeholder::Type => {
+                        name.push_str(image.image_type.strip_prefix("image/").unwrap_or(&image.image_type))
+                    }
+                },
+            }
+        }
+        name
+    }
+}
+
+/// `{width}` / `{height}` 占位符的文字
+fn dimension_text(length: Option<u32>) -> String {
+    length.map_or_else(|| UNKNOWN_DIMENSION.to_string(), |length| length.to_string())
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self::parse(DEFAULT_NAME_TEMPLATE).expect("默认命名模板有效")
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for NameTemplate {
+    type Error = ExtractionError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        Self::parse(&template)
+    }
+}
+
+impl From<NameTemplate> for String {
+    fn from(template: NameTemplate) -> Self {
+        template.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::formats::Integrity;
+
+    fn sample_image(width: Option<u32>, height: Option<u32>) -> ImageInfo<'static> {
+        ImageInfo {
+            index: 7,
+            image_type: "image/png".to_string(),
+            size: 3,
+            data: Cow::Borrowed(b"png"),
+            position: 1234,
+            end_position: 1237,
+            width,
+            height,
+            integrity: Integrity::Valid,
+            repair: None,
+            frame_count: None,
+            content_location: None,
+            original_filename: None,
+            hash: "0123456789abcdef0123456789abcdef".to_string(),
+            perceptual_hash: None,
+        }
+    }
+
+    #[test]
+    fn parse_and_render() {
+        let image = sample_image(Some(640), Some(480));
+        let cases = [
+            ("image_{index}.{ext}", "image_007.png"),
+            ("{source_stem}_{width}x{height}", "page_640x480"),
+            ("{type}@{offset}_{hash}", "png@1234_0123456789abcdef"),
+            ("{{", "{"),
+            ("}}", "}"),
+            ("{{index}}_{index}", "{index}_007"),
+            ("a}}b{{c", "a}b{c"),
+        ];
+        for (template, expected) in cases {
+            let parsed = NameTemplate::parse(template).unwrap_or_else(|e| panic!("{}: {}", template, e));
+            assert_eq!(parsed.render("page", image.index, &image, ".png"), expected, "{}", template);
+            assert_eq!(parsed.to_string(), template);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("{index", "占位符未闭合"),
+            ("image_{", "占位符未闭合"),
+            ("image_}", "多余的 '}'"),
+            ("}{index}", "多余的 '}'"),
+            ("{}", "未知占位符: {}"),
+            ("{size}", "未知占位符: {size}"),
+            ("{Index}", "未知占位符"),
+            ("", "模板不能为空"),
+        ];
+        for (template, expected) in cases {
+            match NameTemplate::parse(template) {
+                Ok(_) => panic!("{} 应解析失败", template),
+                Err(e) => assert!(e.to_string().contains(expected), "{}: {}", template, e),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_dimensions_render_as_unknown() {
+        let image = sample_image(None, None);
+        let template = NameTemplate::parse("{width}x{height}").unwrap();
+        assert_eq!(template.render("page", 0, &image, ".svg"), "unknownxunknown");
+    }
+
+    #[test]
+    fn names_from_locations() {
+        let cases = [
+            ("https://example.com/img/logo.png?v=3#top", Some("logo.png")),
+            ("https://example.com/%E5%9B%BE.png", Some("图.png")),
+            ("https://example.com/a/..%2F..%2Fetc%2Fpasswd", Some("../../etc/passwd")),
+            ("file:///C:/Users/me/photo.jpg", Some("photo.jpg")),
+            ("C:\\Users\\me\\photo.jpg", Some("photo.jpg")),
+            ("https://example.com/", None),
+            ("https://example.com", None),
+            ("https://example.com/?name=a.png", None),
+            ("cid:image001.png@01D9", None),
+            ("DATA:image/png;base64,AAAA", None),
+        ];
+        for (location, expected) in cases {
+            assert_eq!(name_from_location(location).as_deref(), expected, "{}", location);
+        }
+    }
+
+    #[test]
+    fn sanitize_file_names() {
+        let long = format!("{}.png", "a".repeat(150));
+        let truncated = format!("{}.png", "a".repeat(MAX_STEM_CHARS));
+        let cases = [
+            ("../../etc/passwd", Some("_.._etc_passwd")),
+            ("..\\..\\boot.ini", Some("_.._boot.ini")),
+            ("a:b*c?.jpg", Some("a_b_c_.jpg")),
+            ("line\nbreak.gif", Some("line_break.gif")),
+            ("logo.png...", Some("logo.png")),
+            ("  name. ", Some("name")),
+            ("...", None),
+            (" ", None),
+            ("con.png", Some("_con.png")),
+            ("CON", Some("_CON")),
+            ("Lpt1.tar.gz", Some("_Lpt1.tar.gz")),
+            ("nul .jpg", Some("_nul .jpg")),
+            ("console.png", Some("console.png")),
+            ("report.v2.final", Some("report.v2.final")),
+            (long.as_str(), Some(truncated.as_str())),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(sanitize_file_name(name).as_deref(), expected, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn unique_names_ignore_case() {
+        let mut names = UniqueNames::new();
+        let claimed: Vec<_> = ["Logo.PNG", "logo.png", "LOGO_1.png", "logo.png", "README", "readme", ".hidden", ".HIDDEN"]
+            .into_iter()
+            .map(|name| names.claim(name))
+            .collect();
+        assert_eq!(
+            claimed,
+            ["Logo.PNG", "logo_1.png", "LOGO_1_1.png", "logo_2.png", "README", "readme_1", ".hidden", ".HIDDEN_1"]
+        );
+    }
+}
@@ -0,0 +1,542 @@
+//! 提取选项
+//!
+//! 汇总过滤、命名、格式、去重、预览和覆盖策略等设置，贯穿整个提取流程。
+//! 选项可以序列化，支持从TOML或JSON配置文件加载。
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::convert::{ConvertFormat, DEFAULT_QUALITY};
+use crate::dedup::DedupMode;
+use crate::extractor::{is_known_format, ExtractionError};
+use crate::naming::NameTemplate;
+use crate::phash::NearDupAction;
+
+/// 默认最小图片尺寸
+pub const DEFAULT_MIN_SIZE: (u32, u32) = (100, 100);
+
+/// 输出文件已存在时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    /// 覆盖已有文件
+    #[default]
+    Overwrite,
+    /// 保留已有文件，跳过该图片
+    Skip,
+    /// 改用不冲突的文件名（追加序号）
+    Rename,
+}
+
+/// 尺寸过滤规则：宽和高怎样组合判断是否超出范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SizeMatch {
+    /// 宽和高都超出范围才过滤
+    #[default]
+    Both,
+    /// 宽或高任一超出范围即过滤
+    Either,
+}
+
+impl SizeMatch {
+    /// 按规则组合宽、高各自是否超出范围
+    pub fn exceeds(self, width_exceeds: bool, height_exceeds: bool) -> bool {
+        match self {
+            SizeMatch::Both => width_exceeds && height_exceeds,
+            SizeMatch::Either => width_exceeds || height_exceeds,
+        }
+    }
+}
+
+/// 宽高比（宽/高）范围，两端均可省略
+///
+/// 写作 `最小-最大`，每端可以是小数或 `宽:高`，例如 `4:3-16:9`、`1.2-`（横图）、`-1`（竖图和方图）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AspectRange {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// 原始写法，用于显示和序列化
+    source: String,
+}
+
+impl AspectRange {
+    /// 解析宽高比范围
+    pub fn parse(range: &str) -> Result<Self, ExtractionError> {
+        let invalid = || ExtractionError::AspectRange(format!("{}（请使用 最小-最大 格式，例如 4:3-16:9、1.2-）", range));
+        let (min, max) = range.trim().split_once('-').ok_or_else(invalid)?;
+        let bound = |text: &str| -> Result<Option<f64>, ExtractionError> {
+            let text = text.trim();
+            if text.is_empty() {
+                return Ok(None);
+            }
+            let ratio = match text.split_once(':') {
+                Some((width, height)) => {
+                    let width: f64 = width.trim().parse().map_err(|_| invalid())?;
+                    let height: f64 = height.trim().parse().map_err(|_| invalid())?;
+                    width / height
+                }
+                None => text.parse().map_err(|_| invalid())?,
+            };
+            if ratio.is_finite() && ratio > 0.0 {
+                Ok(Some(ratio))
+            } else {
+                Err(invalid())
+            }
+        };
+
+        let (min, max) = (bound(min)?, bound(max)?);
+        if min.is_none() && max.is_none() || min.zip(max).is_some_and(|(min, max)| min > max) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            min,
+            max,
+            source: range.trim().to_string(),
+        })
+    }
+
+    /// 宽高比是否在范围内，高为0（尺寸未知）时视为不在范围内
+    pub fn contains(&self, width: u32, height: u32) -> bool {
+        if height == 0 {
+            return false;
+        }
+        let aspect = f64::from(width) / f64::from(height);
+        self.min.is_none_or(|min| aspect >= min) && self.max.is_none_or(|max| aspect <= max)
+    }
+}
+
+impl fmt::Display for AspectRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for AspectRange {
+    type Err = ExtractionError;
+
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        Self::parse(range)
+    }
+}
+
+impl TryFrom<String> for AspectRange {
+    type Error = ExtractionError;
+
+    fn try_from(range: String) -> Result<Self, Self::Error> {
+        Self::parse(&range)
+    }
+}
+
+impl From<AspectRange> for String {
+    fn from(range: AspectRange) -> Self {
+        range.source
+    }
+}
+
+/// 提取选项
+///
+/// 所有字段都有默认值，配置文件中只需写出要修改的项，例如：
+///
+/// ```toml
+/// min_size = [200, 150]
+/// size_match = "either"
+/// aspect = "4:3-"
+/// formats = ["jpeg", "png"]
+/// name_template = "{source_stem}_{index}.{ext}"
+/// dedup = "link"
+/// overwrite = "rename"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExtractOptions {
+    /// 输出全部图片，不按最小尺寸过滤（其他过滤条件仍然生效）
+    pub output_all: bool,
+    /// 最小尺寸（宽, 高），小于该尺寸的图片会被跳过
+    pub min_size: (u32, u32),
+    /// 最大尺寸（宽, 高），大于该尺寸的图片会被跳过
+    pub max_size: Option<(u32, u32)>,
+    /// 最小、最大尺寸按宽和高都超出还是任一超出判断
+    pub size_match: SizeMatch,
+    /// 图片数据字节数范围
+    pub min_bytes: Option<usize>,
+    pub max_bytes: Option<usize>,
+    /// 宽高比（宽/高）范围
+    pub aspect: Option<AspectRange>,
+    /// 完整解码每张图片，解码失败的图片不输出（`image` 库不支持解码的格式只检查文件头）
+    pub validate: bool,
+    /// 尝试修复无法完整解码的JPEG/PNG（补全结束标记、重新编码可读取的部分），隐含完整解码
+    pub repair: bool,
+    /// 把无法解码（且未能修复）的图片连同原因说明写入输出目录的 `_quarantine` 子目录，隐含完整解码
+    pub quarantine: bool,
+    /// 只提取这些格式（如 `jpeg`、`png`，也接受 `jpg` 等扩展名写法），为空时提取全部格式
+    pub formats: Vec<String>,
+    /// 使用资源原始文件名命名输出图片
+    pub keep_original_names: bool,
+    /// 输出文件命名模板
+    pub name_template: NameTemplate,
+    /// 把其他格式的图片转换为该格式后保存，`None` 时按原始数据保存
+    pub convert_to: Option<ConvertFormat>,
+    /// 转换或缩放后编码为JPEG时的质量（1-100）
+    pub quality: u8,
+    /// 宽或高超过该值的图片等比缩小后保存（代替原图），`None` 时按原尺寸保存
+    pub max_dimension: Option<u32>,
+    /// 在输出目录的 `_thumbnails` 子目录中为每张保存的图片生成不超过该尺寸（宽, 高）的缩略图
+    pub thumbnail: Option<(u32, u32)>,
+    /// 重复图片（内容完全相同）的处理方式
+    pub dedup: DedupMode,
+    /// 相似图片检测的感知哈希距离阈值（0-64），`None` 时不检测
+    pub near_dup_threshold: Option<u32>,
+    /// 相似图片的处理方式
+    pub near_dup_action: NearDupAction,
+    /// 批量提取时同时处理的文件数，0 表示使用全部CPU核心
+    pub jobs: usize,
+    /// 只扫描不写入：不创建输出目录，不保存图片
+    pub dry_run: bool,
+    /// 输出文件已存在时的处理方式
+    pub overwrite: OverwritePolicy,
+    /// 不输出提取过程中的提示信息
+    pub quiet: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            output_all: false,
+            min_size: DEFAULT_MIN_SIZE,
+            max_size: None,
+            size_match: SizeMatch::Both,
+            min_bytes: None,
+            max_bytes: None,
+            aspect: None,
+            validate: false,
+            repair: false,
+            quarantine: false,
+            formats: Vec::new(),
+            keep_original_names: false,
+            name_template: NameTemplate::default(),
+            convert_to: None,
+            quality: DEFAULT_QUALITY,
+            max_dimension: None,
+            thumbnail: None,
+            dedup: DedupMode::Off,
+            near_dup_threshold: None,
+            near_dup_action: NearDupAction::Group,
+            jobs: 1,
+            dry_run: false,
+            overwrite: OverwritePolicy::Overwrite,
+            quiet: false,
+        }
+    }
+}
+
+impl ExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从配置文件加载选项，`.json` 文件按JSON解析，其他按TOML解析
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("读取配置文件失败: {}", path.display()))?;
+        let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let options: Self = if is_json {
+            serde_json::from_str(&text).with_context(|| format!("解析配置文件失败: {}", path.display()))?
+        } else {
+            toml::from_str(&text).with_context(|| format!("解析配置文件失败: {}", path.display()))?
+        };
+        let has_zero = |(width, height): (u32, u32)| width == 0 || height == 0;
+        if has_zero(options.min_size) || options.max_size.is_some_and(has_zero) {
+            anyhow::bail!("配置文件 {} 中的 min_size 和 max_size 的宽高必须大于0", path.display());
+        }
+        if options.near_dup_threshold.is_some_and(|threshold| threshold > 64) {
+            anyhow::bail!("配置文件 {} 中的 near_dup_threshold 必须在 0-64 之间", path.display());
+        }
+        if options.max_dimension == Some(0) || options.thumbnail.is_some_and(|(width, height)| width == 0 || height == 0) {
+            anyhow::bail!("配置文件 {} 中的 max_dimension 和 thumbnail 必须大于0", path.display());
+        }
+        if !(1..=100).contains(&options.quality) {
+            anyhow::bail!("配置文件 {} 中的 quality 必须在 1-100 之间", path.display());
+        }
+        if let Some(format) = options.formats.iter().find(|format| !is_known_format(format)) {
+            anyhow::bail!("配置文件 {} 中的 formats 包含未知格式: {}", path.display(), format);
+        }
+        Ok(options)
+    }
+
+    /// 设置是否输出全部图片（不按尺寸过滤）
+    pub fn with_output_all(mut self, enabled: bool) -> Self {
+        self.output_all = enabled;
+        self
+    }
+
+    /// 设置最小图片尺寸
+    pub fn with_min_size(mut self, width: u32, height: u32) -> Self {
+        self.min_size = (width, height);
+        self
+    }
+
+    /// 设置最大图片尺寸
+    pub fn with_max_size(mut self, width: u32, height: u32) -> Self {
+        self.max_size = Some((width, height));
+        self
+    }
+
+    /// 设置最小、最大尺寸的判断规则
+    pub fn with_size_match(mut self, size_match: SizeMatch) -> Self {
+        self.size_match = size_match;
+        self
+    }
+
+    /// 设置图片数据字节数范围，`None` 表示该端不限
+    pub fn with_byte_range(mut self, min: Option<usize>, max: Option<usize>) -> Self {
+        self.min_bytes = min;
+        self.max_bytes = max;
+        self
+    }
+
+    /// 只提取宽高比在范围内的图片
+    pub fn with_aspect(mut self, aspect: AspectRange) -> Self {
+        self.aspect = Some(aspect);
+        self
+    }
+
+    /// 设置是否完整解码校验每张图片
+    pub fn with_validate(mut self, enabled: bool) -> Self {
+        self.validate = enabled;
+        self
+    }
+
+    /// 设置是否尝试修复无法完整解码的图片
+    pub fn with_repair(mut self, enabled: bool) -> Self {
+        self.repair = enabled;
+        self
+    }
+
+    /// 设置是否隔离无法解码的图片
+    pub fn with_quarantine(mut self, enabled: bool) -> Self {
+        self.quarantine = enabled;
+        self
+    }
+
+    /// 是否需要完整解码每张图片（校验、修复或隔离）
+    pub fn decode_check(&self) -> bool {
+        self.validate || self.repair || self.quarantine
+    }
+
+    /// 只提取指定格式的图片
+    ///
+    /// 无法识别的格式名不匹配任何图片，可以先用 [`is_known_format`](crate::is_known_format) 检查。
+    pub fn with_formats<I, S>(mut self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.formats = formats.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// 设置是否按 `Content-Disposition` 文件名或 `Content-Location` URL命名输出图片
+    ///
+    /// 没有可用原名的图片（如data URI或签名扫描得到的图片）仍按命名模板命名。
+    pub fn with_original_names(mut self, enabled: bool) -> Self {
+        self.keep_original_names = enabled;
+        self
+    }
+
+    /// 设置输出文件命名模板
+    pub fn with_name_template(mut self, template: NameTemplate) -> Self {
+        self.name_template = template;
+        self
+    }
+
+    /// 把其他格式的图片转换为 `format` 格式后保存，`quality`（1-100）为JPEG编码质量
+    ///
+    /// 已是目标格式的图片按原始数据保存，不重新编码；`image` 库不支持解码的格式（如AVIF、SVG）也按原始数据保存。
+    /// 图片数据损坏导致解码失败时记为保存失败。
+    pub fn with_conversion(mut self, format: ConvertFormat, quality: u8) -> Self {
+        self.convert_to = Some(format);
+        self.quality = quality;
+        self
+    }
+
+    /// 宽或高超过 `max_dimension` 的图片等比缩小后保存
+    ///
+    /// 缩小后按原格式重新编码（PNG、JPEG、WebP以外的格式改为PNG，指定了转换格式时按转换格式）；
+    /// `image` 库不支持解码的格式按原尺寸保存。
+    pub fn with_max_dimension(mut self, max_dimension: u32) -> Self {
+        self.max_dimension = Some(max_dimension);
+        self
+    }
+
+    /// 为每张保存的图片生成不超过 `width` x `height` 的缩略图（保持宽高比，不放大）
+    pub fn with_thumbnail(mut self, width: u32, height: u32) -> Self {
+        self.thumbnail = Some((width, height));
+        self
+    }
+
+    /// 设置重复图片（内容完全相同）的处理方式
+    ///
+    /// 去重记录在同一个提取器处理的所有文件之间共享。
+    pub fn with_dedup(mut self, mode: DedupMode) -> Self {
+        self.dedup = mode;
+        self
+    }
+
+    /// 启用相似图片检测
+    ///
+    /// 感知哈希的汉明距离不超过 `threshold`（0-64）的图片视为相似，
+    /// 按 `action` 分组报告或只保留每组第一张。
+    pub fn with_near_duplicates(mut self, threshold: u32, action: NearDupAction) -> Self {
+        self.near_dup_threshold = Some(threshold);
+        self.near_dup_action = action;
+        self
+    }
+
+    /// 设置批量提取时同时处理的文件数，0 表示使用全部CPU核心
+    ///
+    /// 并行处理时每个文件的提示信息在该文件处理完成后按文件顺序整体输出；
+    /// 启用去重或相似图片检测时各文件按路径顺序轮流保存，保留哪一张重复图片与顺序处理时相同，
+    /// 等待轮次的文件至多暂存约 64 MiB 已解码的图片数据。
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// 设置是否只扫描不写入
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// 设置输出文件已存在时的处理方式
+    pub fn with_overwrite(mut self, policy: OverwritePolicy) -> Self {
+        self.overwrite = policy;
+        self
+    }
+
+    /// 设置是否不输出提示信息（提取结果仍记录在清单中）
+    pub fn with_quiet(mut self, enabled: bool) -> Self {
+        self.quiet = enabled;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 把配置写入临时文件后加载，`name` 的扩展名决定按JSON还是TOML解析
+    fn load(name: &str, text: &str) -> Result<ExtractOptions> {
+        let dir = std::env::temp_dir().join(format!("uhtml-pics-parse-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        let options = ExtractOptions::from_file(&path);
+        fs::remove_file(&path).unwrap();
+        options
+    }
+
+    #[test]
+    fn toml_and_json_configs() {
+        let toml = r#"
+            min_size = [200, 150]
+            aspect = "4:3-"
+            formats = ["jpg", "png"]
+            name_template = "{source_stem}_{index}.{ext}"
+            dedup = "link"
+        "#;
+        let json = r#"{
+            "min_size": [200, 150],
+            "aspect": "4:3-",
+            "formats": ["jpg", "png"],
+            "name_template": "{source_stem}_{index}.{ext}",
+            "dedup": "link"
+        }"#;
+        let from_toml = load("config.toml", toml).unwrap();
+        let from_json = load("config.JSON", json).unwrap();
+        assert_eq!(from_toml, from_json);
+
+        let expected = ExtractOptions::new()
+            .with_min_size(200, 150)
+            .with_aspect(AspectRange::parse("4:3-").unwrap())
+            .with_formats(["jpg", "png"])
+            .with_name_template(NameTemplate::parse("{source_stem}_{index}.{ext}").unwrap())
+            .with_dedup(DedupMode::Link);
+        assert_eq!(from_toml, expected);
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let options = ExtractOptions::new()
+            .with_max_size(4000, 3000)
+            .with_aspect(AspectRange::parse("0.5-3:2").unwrap())
+            .with_name_template(NameTemplate::parse("{{{hash}}}_{width}x{height}.{ext}").unwrap())
+            .with_near_duplicates(5, NearDupAction::Drop);
+        assert_eq!(load("saved.toml", &toml::to_string(&options).unwrap()).unwrap(), options);
+        assert_eq!(load("saved.json", &serde_json::to_string(&options).unwrap()).unwrap(), options);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        let cases = [
+            "min_sise = [200, 150]",
+            "min_size = [0, 5]",
+            "max_size = [800, 0]",
+            "formats = [\"jepg\"]",
+            "quality = 0",
+            "near_dup_threshold = 65",
+            "max_dimension = 0",
+            "thumbnail = [0, 100]",
+            "name_template = \"{nope}.{ext}\"",
+            "aspect = \"16:9-4:3\"",
+        ];
+        for text in cases {
+            assert!(load("invalid.toml", text).is_err(), "{} 应加载失败", text);
+        }
+        assert!(load("invalid.json", r#"{"min_sise": [200, 150]}"#).is_err());
+    }
+
+    #[test]
+    fn parse_aspect_ranges() {
+        let cases = [
+            ("4:3-16:9", Some(4.0 / 3.0), Some(16.0 / 9.0)),
+            ("1.2-", Some(1.2), None),
+            ("-1", None, Some(1.0)),
+            (" 1 - 1 ", Some(1.0), Some(1.0)),
+            ("0.5-3:2", Some(0.5), Some(1.5)),
+        ];
+        for (range, min, max) in cases {
+            let parsed = AspectRange::parse(range).unwrap_or_else(|e| panic!("{}: {}", range, e));
+            assert_eq!((parsed.min, parsed.max), (min, max), "{}", range);
+            assert_eq!(parsed.to_string(), range.trim());
+        }
+    }
+
+    #[test]
+    fn invalid_aspect_ranges() {
+        let cases = ["", "-", "1.5", "16:9-4:3", "2-1", "0-1", "1:0-2", "a-b", "4:-3", "1-inf", "NaN-"];
+        for range in cases {
+            assert!(AspectRange::parse(range).is_err(), "{} 应解析失败", range);
+        }
+    }
+
+    #[test]
+    fn aspect_range_contains() {
+        let landscape = AspectRange::parse("4:3-16:9").unwrap();
+        assert!(landscape.contains(1600, 900));
+        assert!(landscape.contains(800, 600));
+        assert!(!landscape.contains(600, 800));
+        assert!(!landscape.contains(100, 0));
+
+        let portrait = AspectRange::parse("-1").unwrap();
+        assert!(portrait.contains(100, 100));
+        assert!(portrait.contains(300, 400));
+        assert!(!portrait.contains(400, 300));
+    }
+}
@@ -0,0 +1,127 @@
+//! 感知哈希相似图片检测
+//!
+//! 使用差值哈希（dHash）：把图片缩小为 9x8 灰度图，比较每行相邻像素的明暗，
+//! 得到64位指纹。同一张图片重新编码、缩放或调整质量后指纹的汉明距离很小。
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+/// 相似图片的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NearDupAction {
+    /// 全部保存，仅在报告中分组列出
+    #[default]
+    Group,
+    /// 只保存每组中第一张，跳过其余相似图片
+    Drop,
+}
+
+/// 计算图片的差值哈希
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// 两个哈希的汉明距离
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 相似图片组中的一张图片
+#[derive(Debug, Clone)]
+pub struct NearDupMember {
+    pub path: PathBuf,
+    /// 与组内第一张图片的汉明距离
+    pub distance: u32,
+    /// 是否已保存（`drop` 模式下除第一张外不保存）
+    pub saved: bool,
+}
+
+/// 一组相似图片，第一张作为代表
+#[derive(Debug, Clone)]
+pub struct NearDupCluster {
+    pub hash: u64,
+    pub members: Vec<NearDupMember>,
+}
+
+/// 相似图片聚类记录，同一个提取器处理的所有文件共用
+#[derive(Debug)]
+pub struct NearDupIndex {
+    /// 视为相似的最大汉明距离
+    threshold: u32,
+    action: NearDupAction,
+    clusters: Mutex<Vec<NearDupCluster>>,
+}
+
+impl NearDupIndex {
+    pub fn new(threshold: u32, action: NearDupAction) -> Self {
+        Self {
+            threshold,
+            action,
+            clusters: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn action(&self) -> NearDupAction {
+        self.action
+    }
+
+    /// 把图片归入距离最近且不超过阈值的组，找不到时新建一组
+    ///
+    /// 返回所属组的代表图片路径和距离；新建组时返回 `None`。
+    pub fn assign(&self, hash: u64, path: &Path) -> Option<(PathBuf, u32)> {
+        let mut clusters = self.clusters.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let nearest = clusters
+            .iter_mut()
+            .map(|cluster| (hamming_distance(cluster.hash, hash), cluster))
+            .filter(|(distance, _)| *distance <= self.threshold)
+            .min_by_key(|(distance, _)| *distance);
+
+        match nearest {
+            Some((distance, cluster)) => {
+                let representative = cluster.members[0].path.clone();
+                cluster.members.push(NearDupMember {
+                    path: path.to_path_buf(),
+                    distance,
+                    saved: self.action == NearDupAction::Group,
+                });
+                Some((representative, distance))
+            }
+            None => {
+                clusters.push(NearDupCluster {
+                    hash,
+                    members: vec![NearDupMember {
+                        path: path.to_path_buf(),
+                        distance: 0,
+                        saved: true,
+                    }],
+                });
+                None
+            }
+        }
+    }
+
+    /// 包含两张及以上图片的组
+    pub fn clusters(&self) -> Vec<NearDupCluster> {
+        let clusters = self.clusters.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        clusters
+            .iter()
+            .filter(|cluster| cluster.members.len() > 1)
+            .cloned()
+            .collect()
+    }
+}
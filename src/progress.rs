@@ -0,0 +1,82 @@
+//! 提取过程中的提示信息
+//!
+//! 顺序处理时直接输出；并行处理多个文件时每个文件的信息先缓存，
+//! 再按文件顺序整体输出，避免不同文件的信息互相交错；
+//! 内存中提取（库接口）时不输出任何信息。
+
+/// 一条提示信息
+#[derive(Debug)]
+enum Line {
+    /// 输出到标准输出
+    Info(String),
+    /// 输出到标准错误
+    Warn(String),
+}
+
+/// 提示信息的输出方式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// 直接输出
+    #[default]
+    Immediate,
+    /// 缓存信息，由 `flush` 统一输出
+    Buffered,
+    /// 丢弃所有信息
+    Silent,
+}
+
+/// 单个文件的提示信息
+#[derive(Debug, Default)]
+pub struct ProgressLog {
+    mode: Mode,
+    lines: Vec<Line>,
+}
+
+impl ProgressLog {
+    /// 直接输出的提示信息
+    pub fn immediate() -> Self {
+        Self::default()
+    }
+
+    /// 缓存到 `flush` 时才输出的提示信息
+    pub fn buffered() -> Self {
+        Self {
+            mode: Mode::Buffered,
+            lines: Vec::new(),
+        }
+    }
+
+    /// 不输出任何信息
+    pub fn silent() -> Self {
+        Self {
+            mode: Mode::Silent,
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        match self.mode {
+            Mode::Immediate => println!("{}", message.into()),
+            Mode::Buffered => self.lines.push(Line::Info(message.into())),
+            Mode::Silent => {}
+        }
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        match self.mode {
+            Mode::Immediate => eprintln!("{}", message.into()),
+            Mode::Buffered => self.lines.push(Line::Warn(message.into())),
+            Mode::Silent => {}
+        }
+    }
+
+    /// 按记录顺序输出缓存的信息
+    pub fn flush(&mut self) {
+        for line in self.lines.drain(..) {
+            match line {
+                Line::Info(message) => println!("{}", message),
+                Line::Warn(message) => eprintln!("{}", message),
+            }
+        }
+    }
+}
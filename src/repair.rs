@@ -0,0 +1,98 @@
+//! 损坏图片修复
+//!
+//! 截断的JPEG补上结束标记（EOI），解码器会把缺失的扫描数据当作空白；
+//! 截断或校验出错的PNG逐行解码出尚能读取的部分，其余行留空后重新编码为PNG。
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageBuffer, ImageOutputFormat};
+use serde::Serialize;
+
+/// 重新编码时允许的最大像素数，防止损坏的文件头声明超大尺寸而耗尽内存
+const MAX_REENCODE_PIXELS: u64 = 100_000_000;
+
+/// 图片的修复方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Repair {
+    /// 补全缺失的JPEG结束标记
+    AppendedEoi,
+    /// 解码可读取的部分后重新编码
+    Reencoded,
+}
+
+impl Repair {
+    /// 用于输出的中文描述
+    pub fn label(&self) -> &'static str {
+        match self {
+            Repair::AppendedEoi => "补全结束标记",
+            Repair::Reencoded => "重新编码可读取的部分",
+        }
+    }
+}
+
+/// 尝试修复无法完整解码的图片，返回修复后的数据和修复方式
+///
+/// 目前支持JPEG和PNG；修复后的数据必须能完整解码，否则视为无法修复。
+pub fn repair(image_type: &str, data: &[u8]) -> Option<(Vec<u8>, Repair)> {
+    match image_type {
+        "image/jpeg" => repair_jpeg(data),
+        "image/png" => reencode_png(data).map(|data| (data, Repair::Reencoded)),
+        _ => None,
+    }
+}
+
+fn repair_jpeg(data: &[u8]) -> Option<(Vec<u8>, Repair)> {
+    const EOI: [u8; 2] = [0xFF, 0xD9];
+    if data.ends_with(&EOI) {
+        return None;
+    }
+
+    let mut patched = Vec::with_capacity(data.len() + EOI.len());
+    patched.extend_from_slice(data);
+    patched.extend_from_slice(&EOI);
+    image::load_from_memory(&patched).ok()?;
+    Some((patched, Repair::AppendedEoi))
+}
+
+/// 逐行解码PNG直到出错，把已解码的行重新编码（不支持隔行扫描的PNG）
+fn reencode_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    decoder.ignore_checksums(true);
+    let mut reader = decoder.read_info().ok()?;
+
+    let info = reader.info();
+    let (width, height) = (info.width, info.height);
+    if info.interlaced || u64::from(width) * u64::from(height) > MAX_REENCODE_PIXELS {
+        return None;
+    }
+
+    let (color_type, _) = reader.output_color_type();
+    let line_size = reader.output_line_size(width);
+    let mut pixels = vec![0; line_size * height as usize];
+    let mut decoded_rows = 0;
+    for line in pixels.chunks_exact_mut(line_size) {
+        match reader.next_row() {
+            Ok(Some(row)) => line.copy_from_slice(row.data()),
+            _ => break,
+        }
+        decoded_rows += 1;
+    }
+    if decoded_rows == 0 {
+        return None;
+    }
+
+    let image = match color_type {
+        png::ColorType::Grayscale => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        png::ColorType::GrayscaleAlpha => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        png::ColorType::Rgb => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        png::ColorType::Rgba => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8),
+        // 调色板图片已被展开为RGB(A)
+        png::ColorType::Indexed => None,
+    }?;
+
+    let mut encoded = Cursor::new(Vec::new());
+    image.write_to(&mut encoded, ImageOutputFormat::Png).ok()?;
+    Some(encoded.into_inner())
+}
@@ -0,0 +1,162 @@
+//! 图片格式签名及签名扫描
+//!
+//! 用一个Aho-Corasick自动机一次扫描数据即可找出所有格式的魔数，
+//! 不必为每种签名各扫描一遍。
+
+use std::sync::OnceLock;
+
+use aho_corasick::{AhoCorasick, FindOverlappingIter};
+
+use crate::formats::{self, ImageSpan};
+
+/// 图片格式签名
+pub struct ImageSignature {
+    /// 魔数
+    pub magic: &'static [u8],
+    /// 魔数相对图片起始位置的偏移（ISO-BMFF的 `ftyp` 位于盒子长度之后）
+    pub offset: usize,
+    pub image_type: &'static str,
+    /// 结束标记，仅在结构解析失败时用于估计边界；没有结束标记的格式必须通过结构解析
+    pub footer: Option<&'static [u8]>,
+    /// 结构解析函数
+    pub parse: fn(&[u8], usize) -> Option<ImageSpan>,
+}
+
+pub const IMAGE_SIGNATURES: &[ImageSignature] = &[
+    ImageSignature { magic: b"\xFF\xD8\xFF", offset: 0, image_type: "jpeg", footer: Some(b"\xFF\xD9"), parse: formats::jpeg_span },
+    ImageSignature { magic: b"\x89PNG\r\n\x1a\n", offset: 0, image_type: "png", footer: Some(b"IEND\xaeB`\x82"), parse: formats::png_span },
+    ImageSignature { magic: b"GIF87a", offset: 0, image_type: "gif", footer: Some(b"\x00\x3B"), parse: formats::gif_span },
+    ImageSignature { magic: b"GIF89a", offset: 0, image_type: "gif", footer: Some(b"\x00\x3B"), parse: formats::gif_span },
+    ImageSignature { magic: b"RIFF", offset: 0, image_type: "webp", footer: None, parse: formats::webp_span },
+    ImageSignature { magic: b"BM", offset: 0, image_type: "bmp", footer: None, parse: formats::bmp_span },
+    ImageSignature { magic: b"II*\0", offset: 0, image_type: "tiff", footer: None, parse: formats::tiff_span },
+    ImageSignature { magic: b"MM\0*", offset: 0, image_type: "tiff", footer: None, parse: formats::tiff_span },
+    ImageSignature { magic: b"\0\0\x01\0", offset: 0, image_type: "x-icon", footer: None, parse: formats::ico_span },
+    ImageSignature { magic: b"\0\0\x02\0", offset: 0, image_type: "x-cursor", footer: None, parse: formats::ico_span },
+    ImageSignature { magic: b"ftyp", offset: 4, image_type: "avif", footer: None, parse: formats::isobmff_span },
+];
+
+/// 匹配所有签名魔数的自动机，模式编号与 `IMAGE_SIGNATURES` 的下标一致
+fn signature_matcher() -> &'static AhoCorasick {
+    static MATCHER: OnceLock<AhoCorasick> = OnceLock::new();
+    MATCHER.get_or_init(|| {
+        AhoCorasick::new(IMAGE_SIGNATURES.iter().map(|signature| signature.magic)).expect("图片签名可以构建自动机")
+    })
+}
+
+/// 单次扫描找出数据中所有签名的位置（图片起始位置），按位置排序
+///
+/// 签名之间允许重叠（如JPEG数据中出现的 `BM`），同一位置的多个签名按签名表顺序排列。
+pub fn find_signatures(data: &[u8]) -> Vec<(usize, &'static ImageSignature)> {
+    let mut positions: Vec<(usize, usize)> = signature_matcher()
+        .find_overlapping_iter(data)
+        .filter_map(|found| {
+            let index = found.pattern().as_usize();
+            let start = found.start().checked_sub(IMAGE_SIGNATURES[index].offset)?;
+            Some((start, index))
+        })
+        .collect();
+    positions.sort_unstable();
+    positions
+        .into_iter()
+        .map(|(start, index)| (start, &IMAGE_SIGNATURES[index]))
+        .collect()
+}
+
+/// 按位置顺序逐个产出签名位置，只在需要时继续向后扫描
+///
+/// 与 [`find_signatures`] 的结果相同，但找到第一张图片不必先扫描完整个文件。
+pub struct SignatureCursor<'a> {
+    matches: FindOverlappingIter<'static, 'a>,
+    /// 已找到的签名位置 (起始位置, 签名下标)；前 `settled` 个已排序，之后不会再找到更靠前的位置
+    positions: Vec<(usize, usize)>,
+    settled: usize,
+}
+
+impl<'a> SignatureCursor<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            matches: signature_matcher().find_overlapping_iter(data),
+            positions: Vec::new(),
+            settled: 0,
+        }
+    }
+
+    /// 第 `index` 个签名位置（按位置排序），没有更多签名时为 `None`
+    pub fn get(&mut self, index: usize) -> Option<(usize, &'static ImageSignature)> {
+        while index >= self.settled {
+            match self.matches.next() {
+                Some(found) => {
+                    let pattern = found.pattern().as_usize();
+                    if let Some(start) = found.start().checked_sub(IMAGE_SIGNATURES[pattern].offset) {
+                        self.positions.push((start, pattern));
+                    }
+                    // 匹配按结束位置顺序产出，之后的签名起始位置不会早于当前结束位置减去最长的魔数加偏移
+                    self.settle(found.end().saturating_sub(max_signature_reach()));
+                }
+                None => {
+                    self.settle(usize::MAX);
+                    if index >= self.settled {
+                        return None;
+                    }
+                }
+            }
+        }
+        let (start, pattern) = self.positions[index];
+        Some((start, &IMAGE_SIGNATURES[pattern]))
+    }
+
+    /// 对尚未确定顺序的位置排序，确定起始位置在 `bound` 之前的部分
+    fn settle(&mut self, bound: usize) {
+        let pending = &mut self.positions[self.settled..];
+        pending.sort_unstable();
+        self.settled += pending.iter().take_while(|&&(start, _)| start < bound).count();
+    }
+}
+
+/// 魔数长度加偏移的最大值
+fn max_signature_reach() -> usize {
+    IMAGE_SIGNATURES.iter().map(|signature| signature.offset + signature.magic.len()).max().unwrap_or(0)
+}
+
+/// 签名扫描加结构解析，跳过位于已解析图片内部的签名，返回能通过结构解析的图片数
+///
+/// 与提取时的扫描过程相同但不复制图片数据，供性能测试使用。
+pub fn scan_and_parse(data: &[u8]) -> usize {
+    let mut found = 0;
+    let mut covered_until = 0;
+    for (pos, signature) in find_signatures(data) {
+        if pos < covered_until {
+            continue;
+        }
+        if let Some(span) = (signature.parse)(data, pos) {
+            covered_until = span.end;
+            found += 1;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_matches_full_scan() {
+        let mut data = b"<html>\0\0\0\x1cftypavif".to_vec();
+        data.extend_from_slice(b"BM\xFF\xD8\xFF\xE0II*\0GIF89a\x89PNG\r\n\x1a\nRIFF");
+        data.extend_from_slice(b"xxftyp\0\0\x01\0\0\0\x02\0MM\0*");
+        let expected = find_signatures(&data);
+        assert!(expected.len() >= 10);
+
+        let mut cursor = SignatureCursor::new(&data);
+        let found: Vec<_> = (0..).map_while(|i| cursor.get(i)).collect();
+        assert_eq!(
+            found.iter().map(|&(pos, signature)| (pos, signature.magic)).collect::<Vec<_>>(),
+            expected.iter().map(|&(pos, signature)| (pos, signature.magic)).collect::<Vec<_>>(),
+        );
+        // 已产出的位置可以重复读取
+        assert_eq!(cursor.get(0).map(|(pos, _)| pos), Some(expected[0].0));
+        assert!(cursor.get(expected.len()).is_none());
+    }
+}
@@ -0,0 +1,47 @@
+//! 源文件读取
+//!
+//! 优先以内存映射方式打开UHTML文件，扫描和保存图片时直接使用映射的字节范围，
+//! 不把整个文件读入内存；无法映射时（如管道、特殊文件系统）回退到完整读取。
+
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+/// 源文件内容
+pub enum SourceData {
+    /// 内存映射的文件
+    Mapped(Mmap),
+    /// 读入内存的文件
+    Owned(Vec<u8>),
+}
+
+impl SourceData {
+    /// 打开源文件，优先使用内存映射
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        // 安全性: 映射期间源文件被其他进程截断会导致访问出错；提取过程假定源文件只读不变，
+        // 被修改时读到的不一致数据由解析代码的边界检查处理
+        match unsafe { Mmap::map(&file) } {
+            Ok(map) => Ok(SourceData::Mapped(map)),
+            Err(_) => {
+                let mut data = Vec::new();
+                file.read_to_end(&mut data)?;
+                Ok(SourceData::Owned(data))
+            }
+        }
+    }
+}
+
+impl Deref for SourceData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SourceData::Mapped(map) => map,
+            SourceData::Owned(data) => data,
+        }
+    }
+}
@@ -29,7 +29,6 @@ impl Updater {
         
         let version_str = String::from_utf8_lossy(&output.stdout);
         let version = version_str
-            .trim()
             .split_whitespace()
             .last()
             .ok_or_else(|| anyhow!("无法解析当前版本"))?;